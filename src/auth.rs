@@ -0,0 +1,120 @@
+use git2::{Cred, CredentialType, ErrorClass, ErrorCode};
+use std::{env, path::PathBuf};
+
+/// Environment variable holding an access token for private templates. It
+/// can also be set in a `.env` file.
+pub const TOKEN_ENV: &str = "GENESIS_GIT_TOKEN";
+/// Environment variables pointing at an SSH key, and its passphrase, to try
+/// before the default keys in `~/.ssh`.
+pub const SSH_KEY_ENV: &str = "GENESIS_SSH_KEY";
+pub const SSH_PASSPHRASE_ENV: &str = "GENESIS_SSH_PASSPHRASE";
+
+/// Answers libgit2's credential requests, offering each method at most
+/// once. libgit2 keeps asking for as long as credentials are handed out, so
+/// running out of methods is what ends a failed authentication.
+pub struct Authenticator {
+    token: Option<String>,
+    ssh_keys: Vec<PathBuf>,
+    tried_agent: bool,
+    tried_helper: bool,
+    tried_default: bool,
+    tried: Vec<String>,
+}
+
+impl Authenticator {
+    /// `token` is used for HTTPS remotes, falling back to `GENESIS_GIT_TOKEN`.
+    pub fn new(token: Option<&str>) -> Self {
+        let token = token
+            .map(str::to_string)
+            .or_else(|| env::var(TOKEN_ENV).ok())
+            .filter(|token| !token.is_empty());
+
+        let mut ssh_keys: Vec<PathBuf> = env::var(SSH_KEY_ENV)
+            .ok()
+            .map(|key| PathBuf::from(shellexpand::tilde(&key).into_owned()))
+            .into_iter()
+            .collect();
+        if let Some(ssh_dir) = dirs::home_dir().map(|home| home.join(".ssh")) {
+            for name in ["id_ed25519", "id_ecdsa", "id_rsa"] {
+                ssh_keys.push(ssh_dir.join(name));
+            }
+        }
+        // Keys are popped off the end.
+        ssh_keys.reverse();
+
+        Authenticator {
+            token,
+            ssh_keys,
+            tried_agent: false,
+            tried_helper: false,
+            tried_default: false,
+            tried: Vec::new(),
+        }
+    }
+
+    pub fn credentials(
+        &mut self,
+        url: &str,
+        username_from_url: Option<&str>,
+        allowed: CredentialType,
+    ) -> Result<Cred, git2::Error> {
+        let username = username_from_url.unwrap_or("git");
+
+        if allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(username);
+        }
+
+        if allowed.contains(CredentialType::SSH_KEY) {
+            if !self.tried_agent {
+                self.tried_agent = true;
+                self.tried.push("ssh-agent".to_string());
+                return Cred::ssh_key_from_agent(username);
+            }
+
+            while let Some(key) = self.ssh_keys.pop() {
+                if key.exists() {
+                    self.tried.push(key.display().to_string());
+                    let passphrase = env::var(SSH_PASSPHRASE_ENV).ok();
+                    return Cred::ssh_key(username, None, &key, passphrase.as_deref());
+                }
+            }
+        }
+
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            if let Some(token) = self.token.take() {
+                self.tried.push("access token".to_string());
+                return Cred::userpass_plaintext(username, &token);
+            }
+
+            if !self.tried_helper {
+                self.tried_helper = true;
+                if let Ok(cred) = git2::Config::open_default()
+                    .and_then(|config| Cred::credential_helper(&config, url, username_from_url))
+                {
+                    self.tried.push("git credential helper".to_string());
+                    return Ok(cred);
+                }
+            }
+        }
+
+        if allowed.contains(CredentialType::DEFAULT) && !self.tried_default {
+            self.tried_default = true;
+            self.tried.push("default credentials".to_string());
+            return Cred::default();
+        }
+
+        let tried = if self.tried.is_empty() {
+            "no credentials were available".to_string()
+        } else {
+            format!("tried {}", self.tried.join(", "))
+        };
+        Err(git2::Error::new(
+            ErrorCode::Auth,
+            ErrorClass::Net,
+            format!(
+                "Authentication failed for {} ({}). Set {} or configure an SSH key or git credential helper.",
+                url, tried, TOKEN_ENV
+            ),
+        ))
+    }
+}
@@ -0,0 +1,151 @@
+use console::style;
+use git2::Repository;
+use std::{fs, io, path::PathBuf, time::SystemTime};
+
+use crate::error::GenesisError;
+use crate::git::update_mirror;
+use crate::output::say;
+use crate::registry::Registry;
+
+/// Where bare mirrors of templates are kept, e.g. `~/.cache/genesis`.
+pub fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("genesis"))
+}
+
+/// The mirror a template URL is cached in, named after the URL so the
+/// cache stays readable, e.g. `github.com-acme-starter.git`.
+pub fn mirror_path(url: &str) -> Option<PathBuf> {
+    let name = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let name: String = name
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let name = name.trim_matches('-');
+    cache_dir().map(|dir| dir.join("templates").join(format!("{}.git", name)))
+}
+
+/// The URL and location of every cached mirror.
+pub fn cached_mirrors() -> Result<Vec<(String, PathBuf)>, GenesisError> {
+    let Some(templates_dir) = cache_dir().map(|dir| dir.join("templates")) else {
+        return Ok(Vec::new());
+    };
+    if !templates_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut mirrors = Vec::new();
+    for entry in fs::read_dir(templates_dir)? {
+        let path = entry?.path();
+        let Ok(repo) = Repository::open_bare(&path) else {
+            continue;
+        };
+        if let Some(url) = repo
+            .find_remote("origin")
+            .ok()
+            .and_then(|remote| remote.url().map(str::to_string))
+        {
+            mirrors.push((url, path));
+        }
+    }
+    mirrors.sort();
+    Ok(mirrors)
+}
+
+pub fn list_cache() -> Result<(), GenesisError> {
+    let mirrors = cached_mirrors()?;
+    if mirrors.is_empty() {
+        say!("{}", style("No templates are cached yet.").yellow());
+        return Ok(());
+    }
+
+    for (url, path) in mirrors {
+        let updated = fs::metadata(path.join("FETCH_HEAD"))
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .map(|age| format_age(age.as_secs()))
+            .unwrap_or_else(|| "never".to_string());
+
+        say!("{}", style(&url).cyan().bold());
+        say!("  {} {}", style("Path:").dim(), path.display());
+        say!("  {} {}", style("Updated:").dim(), updated);
+    }
+    Ok(())
+}
+
+/// Mirrors every template in the registry, and brings every other cached
+/// template up to date, so projects can be generated offline afterwards.
+/// Templates in local directories are copied rather than cloned, so they
+/// have nothing to cache.
+pub fn update_cache(registry: &Registry) -> Result<(), GenesisError> {
+    let mut urls: Vec<(String, Option<String>)> = registry
+        .templates()
+        .iter()
+        .filter(|t| t.local_dir().is_none())
+        .map(|t| (t.url.clone(), t.token.clone()))
+        .collect();
+    for (url, _) in cached_mirrors()? {
+        if !urls.iter().any(|(u, _)| *u == url) {
+            urls.push((url, None));
+        }
+    }
+
+    let mut failed = 0;
+    for (url, token) in &urls {
+        let Some(mirror) = mirror_path(url) else {
+            return Err(GenesisError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                "Could not determine the cache directory",
+            )));
+        };
+
+        say!("{}", style(format!("Updating {}...", url)).cyan());
+        match update_mirror(url, &mirror, true, token.as_deref()) {
+            Ok(()) => say!("{} {}", style("✓").green().bold(), url),
+            Err(e) => {
+                failed += 1;
+                say!("{} {}: {}", style("✗").red().bold(), url, e.message());
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(GenesisError::Clone(git2::Error::from_str(&format!(
+            "Failed to update {} of {} templates",
+            failed,
+            urls.len()
+        ))));
+    }
+    Ok(())
+}
+
+pub fn clean_cache() -> Result<(), GenesisError> {
+    match cache_dir().filter(|dir| dir.exists()) {
+        Some(dir) => {
+            fs::remove_dir_all(&dir)?;
+            say!(
+                "{}",
+                style(format!("Removed template cache at {}", dir.display())).green()
+            );
+        }
+        None => say!("{}", style("The template cache is already empty.").yellow()),
+    }
+    Ok(())
+}
+
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} minutes ago", secs / 60),
+        3600..=86399 => format!("{} hours ago", secs / 3600),
+        _ => format!("{} days ago", secs / 86400),
+    }
+}
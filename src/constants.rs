@@ -1,4 +1,7 @@
 pub const RUST_URL: &str = "https://github.com/ThembinkosiThemba/rust-project-starter.git";
 pub const GO_URL: &str = "https://github.com/ThembinkosiThemba/go-project-starter.git";
-pub const REACT_VITE_TYPESCRIPT_URL: &str = "https://github.com/ThembinkosiThemba/react_typescript_starter_code.git";
+pub const REACT_VITE_TYPESCRIPT_URL: &str =
+    "https://github.com/ThembinkosiThemba/react_typescript_starter_code.git";
 pub const OLD_MODULE_NAME: &str = "github.com/ThembinkosiThemba/go-project-starter";
+pub const TEMPLATE_LOCK_FILE: &str = ".genesis.toml";
+pub const GENESIS_IGNORE_FILE: &str = ".genesisignore";
@@ -0,0 +1,138 @@
+use console::style;
+use std::{env, fmt::Display, fs};
+use walkdir::WalkDir;
+
+use crate::auth::{SSH_KEY_ENV, TOKEN_ENV};
+use crate::cache::{cache_dir, cached_mirrors, mirror_path};
+use crate::error::GenesisError;
+use crate::git::check_remote;
+use crate::output::say;
+use crate::registry::{registry_path, Registry};
+use crate::toolchain::{detect_version, find_program, TOOLS};
+
+/// Reports everything about the environment that affects generating
+/// projects, to paste into bug reports. Problems are listed rather than
+/// returned, so one broken piece doesn't hide the rest.
+pub fn run_doctor() -> Result<(), GenesisError> {
+    section("Toolchains");
+    for tool in TOOLS {
+        match find_program(tool.name) {
+            Some(path) => ok(format!(
+                "{} {} ({})",
+                tool.name,
+                detect_version(tool.name).unwrap_or_else(|| "(unknown version)".to_string()),
+                path.display()
+            )),
+            None => problem(format!("{} not found", tool.name)),
+        }
+    }
+
+    section("Configuration");
+    let registry = match registry_path() {
+        Some(path) if path.exists() => match Registry::load() {
+            Ok(registry) => {
+                ok(format!("Template registry: {}", path.display()));
+                Some(registry)
+            }
+            Err(e) => {
+                problem(format!("Template registry: {}", e));
+                None
+            }
+        },
+        Some(path) => {
+            info(format!(
+                "Template registry: {} (not created, only the built-in templates are available)",
+                path.display()
+            ));
+            Registry::load().ok()
+        }
+        None => {
+            problem("Template registry: no config directory on this platform");
+            Registry::load().ok()
+        }
+    };
+    for var in [TOKEN_ENV, SSH_KEY_ENV] {
+        match env::var(var) {
+            Ok(value) if !value.is_empty() => info(format!("{} is set", var)),
+            _ => info(format!("{} is not set", var)),
+        }
+    }
+    match dirs::desktop_dir() {
+        Some(desktop) if desktop.is_dir() => ok(format!(
+            "Desktop directory: {} (default project path)",
+            desktop.display()
+        )),
+        Some(desktop) => problem(format!(
+            "Desktop directory {} does not exist; pass --path to choose where projects go",
+            desktop.display()
+        )),
+        None => info("No desktop directory, projects default to the current directory"),
+    }
+
+    section("Template sources");
+    for template in registry.iter().flat_map(|r| r.templates()) {
+        let cached = mirror_path(&template.url).is_some_and(|m| m.exists());
+        let cached = if cached { ", cached" } else { "" };
+        if let Some(dir) = template.local_dir() {
+            ok(format!(
+                "{}: local directory {}",
+                template.name,
+                dir.display()
+            ));
+            continue;
+        }
+        match check_remote(&template.url, template.token.as_deref()) {
+            Ok(()) => ok(format!(
+                "{}: {} reachable{}",
+                template.name, template.url, cached
+            )),
+            Err(e) => problem(format!(
+                "{}: {} unreachable{} ({})",
+                template.name,
+                template.url,
+                cached,
+                e.message()
+            )),
+        }
+    }
+
+    section("Cache");
+    match cache_dir() {
+        Some(dir) if dir.exists() => {
+            let mirrors = cached_mirrors()?;
+            let size: u64 = WalkDir::new(&dir)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter_map(|e| fs::metadata(e.path()).ok())
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+                .sum();
+            ok(format!(
+                "{}: {} templates, {:.1} MB",
+                dir.display(),
+                mirrors.len(),
+                size as f64 / 1_000_000.0
+            ));
+        }
+        Some(dir) => info(format!("{}: empty", dir.display())),
+        None => problem("No cache directory on this platform, templates are always cloned"),
+    }
+
+    Ok(())
+}
+
+fn section(title: &str) {
+    say!("\n{}", style(title).cyan().bold());
+}
+
+fn ok(message: impl Display) {
+    say!("  {} {}", style("✓").green().bold(), message);
+}
+
+fn info(message: impl Display) {
+    say!("  {} {}", style("•").dim(), message);
+}
+
+fn problem(message: impl Display) {
+    say!("  {} {}", style("✗").red().bold(), message);
+}
@@ -0,0 +1,163 @@
+use std::{fmt, io, process::ExitStatus};
+
+/// Everything that can make genesis fail. Each kind of failure exits with
+/// its own code, so scripts wrapping genesis can tell them apart.
+#[derive(Debug)]
+pub enum GenesisError {
+    /// Reading or writing files failed.
+    Io(io::Error),
+    /// A flag, prompt answer or `--var` value is invalid, or a required
+    /// value is missing.
+    Validation(String),
+    /// The template could not be fetched or checked out.
+    Clone(git2::Error),
+    /// The template's remote rejected every credential that was tried.
+    Auth(git2::Error),
+    /// The template, its manifest or the registry is invalid, or doesn't
+    /// fit the project being generated.
+    Template(String),
+    /// A program the setup needs is not installed.
+    ToolMissing(String),
+    /// A program the setup needs is older than the template requires.
+    ToolOutdated {
+        tool: String,
+        found: String,
+        required: String,
+    },
+    /// A setup command exited unsuccessfully.
+    CommandFailed { command: String, status: ExitStatus },
+    /// A hook exited unsuccessfully.
+    HookFailed {
+        hook: String,
+        command: String,
+        status: ExitStatus,
+    },
+    /// The new project's git repository could not be created.
+    Git(git2::Error),
+}
+
+impl GenesisError {
+    /// The error for a `program` that could not be started, telling a
+    /// missing program apart from other failures.
+    pub fn spawn(program: &str, e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => GenesisError::ToolMissing(program.to_string()),
+            _ => GenesisError::Io(e),
+        }
+    }
+
+    /// A short name for the kind of error, used in JSON output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GenesisError::Io(_) => "io",
+            GenesisError::Validation(_) => "validation",
+            GenesisError::Template(_) => "template",
+            GenesisError::Clone(_) => "clone",
+            GenesisError::Auth(_) => "auth",
+            GenesisError::ToolMissing(_) => "tool_missing",
+            GenesisError::ToolOutdated { .. } => "tool_outdated",
+            GenesisError::Git(_) => "git",
+            GenesisError::CommandFailed { .. } => "command_failed",
+            GenesisError::HookFailed { .. } => "hook_failed",
+        }
+    }
+
+    /// The process exit code for this error. 2 is shared with invalid
+    /// command line usage, which clap reports.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            GenesisError::Io(_) => 1,
+            GenesisError::Validation(_) => 2,
+            GenesisError::Template(_) => 3,
+            GenesisError::Clone(_) => 4,
+            GenesisError::Auth(_) => 5,
+            GenesisError::ToolMissing(_) | GenesisError::ToolOutdated { .. } => 6,
+            GenesisError::Git(_) => 7,
+            GenesisError::CommandFailed { .. } => 8,
+            GenesisError::HookFailed { .. } => 9,
+        }
+    }
+}
+
+impl fmt::Display for GenesisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenesisError::Io(e) => write!(f, "{}", e),
+            GenesisError::Validation(message) | GenesisError::Template(message) => {
+                write!(f, "{}", message)
+            }
+            GenesisError::Clone(e) => write!(f, "Could not clone the template: {}", e.message()),
+            GenesisError::Auth(e) => write!(f, "{}", e.message()),
+            GenesisError::ToolMissing(tool) => {
+                write!(f, "`{}` is required but was not found on your PATH", tool)
+            }
+            GenesisError::ToolOutdated {
+                tool,
+                found,
+                required,
+            } => write!(
+                f,
+                "`{}` {} is installed, but this template requires {} or newer",
+                tool, found, required
+            ),
+            GenesisError::CommandFailed { command, status } => {
+                write!(f, "`{}` failed ({})", command, status)
+            }
+            GenesisError::HookFailed {
+                hook,
+                command,
+                status,
+            } => write!(
+                f,
+                "The {} hook `{}` failed ({}); its output is above. Pass --no-hooks to skip hooks",
+                hook, command, status
+            ),
+            GenesisError::Git(e) => {
+                write!(
+                    f,
+                    "Could not set up the project's repository: {}",
+                    e.message()
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for GenesisError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GenesisError::Io(e) => Some(e),
+            GenesisError::Clone(e) | GenesisError::Auth(e) | GenesisError::Git(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for GenesisError {
+    fn from(e: io::Error) -> Self {
+        GenesisError::Io(e)
+    }
+}
+
+impl From<walkdir::Error> for GenesisError {
+    fn from(e: walkdir::Error) -> Self {
+        GenesisError::Io(e.into())
+    }
+}
+
+impl From<ignore::Error> for GenesisError {
+    fn from(e: ignore::Error) -> Self {
+        GenesisError::Io(io::Error::other(e))
+    }
+}
+
+/// Errors from libgit2 come from fetching and checking out templates,
+/// except where they're mapped to `Git` explicitly.
+impl From<git2::Error> for GenesisError {
+    fn from(e: git2::Error) -> Self {
+        match e.code() {
+            git2::ErrorCode::Auth => GenesisError::Auth(e),
+            _ => GenesisError::Clone(e),
+        }
+    }
+}
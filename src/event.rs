@@ -0,0 +1,88 @@
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::hooks::HookStage;
+
+/// Something that happened while generating a project, for tools that
+/// report progress their own way. Events are handed to the callback given
+/// to [`Generator::on_event`](crate::Generator::on_event), in order, and
+/// serialize to the objects printed by `--output json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Event {
+    /// The template to generate from was picked.
+    TemplateResolved {
+        name: String,
+        language: String,
+        url: String,
+        #[serde(rename = "ref")]
+        git_ref: Option<String>,
+        path: Option<String>,
+    },
+    /// The template was cloned, at `commit` when it's a git repository.
+    CloneDone {
+        url: String,
+        #[serde(rename = "ref")]
+        git_ref: Option<String>,
+        path: Option<String>,
+        commit: Option<String>,
+    },
+    /// Files were changed to fit the project, relative to its root.
+    FilesRewritten {
+        action: RewriteAction,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        placeholder: Option<String>,
+        files: Vec<String>,
+    },
+    /// A setup command finished. `exit_code` is `None` when it was killed
+    /// by a signal.
+    Command {
+        command: String,
+        exit_code: Option<i32>,
+    },
+    /// A hook finished. `exit_code` is `None` when it was killed by a
+    /// signal.
+    Hook {
+        hook: HookStage,
+        command: String,
+        exit_code: Option<i32>,
+    },
+}
+
+/// Why files were rewritten.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RewriteAction {
+    /// The Go module path was replaced.
+    ModuleName,
+    /// A placeholder declared in the template's manifest was replaced.
+    Replace,
+    /// `{{variable}}` templates were rendered.
+    Render,
+    /// The package name in `Cargo.toml` was set.
+    PackageName,
+    /// The Go starter was switched to the chosen database.
+    Database,
+}
+
+pub(crate) type Handler = Arc<dyn Fn(&Event) + Send + Sync>;
+
+/// Where a generator's events go: the callback given to
+/// [`Generator::on_event`](crate::Generator::on_event), if any. Passed
+/// along with the rest of the [`SetupOptions`](crate::setup::SetupOptions),
+/// so generators running side by side each get their own events.
+#[derive(Clone, Default)]
+pub(crate) struct Events(Option<Handler>);
+
+impl Events {
+    pub(crate) fn new(handler: Handler) -> Self {
+        Events(Some(handler))
+    }
+
+    pub(crate) fn emit(&self, event: Event) {
+        if let Some(handler) = &self.0 {
+            handler(&event);
+        }
+    }
+}
@@ -1,39 +1,148 @@
+use ignore::WalkBuilder;
 use std::{
     fs,
     io::{Error as IoError, Read, Write},
-    path::Path,
+    path::{Path, PathBuf},
 };
 use walkdir::WalkDir;
 
+use crate::constants::GENESIS_IGNORE_FILE;
+use crate::error::GenesisError;
+use crate::event::{Event, RewriteAction};
+use crate::output::say;
+use crate::progress;
+use crate::setup::SetupOptions;
+use crate::utils::print_dry_run;
+
 pub fn update_module_name(
     project_path: &Path,
     old_module_name: &str,
     new_module_name: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    for entry in WalkDir::new(project_path)
+    options: &SetupOptions,
+) -> Result<(), GenesisError> {
+    let dry_run = options.dry_run;
+    let files: Vec<_> = WalkDir::new(project_path)
         .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
         .filter_map(|e| e.ok())
-    {
+        .filter(|e| e.file_type().is_file())
+        .collect();
+
+    let pb = progress::bar(files.len() as u64, "Updating module name...");
+    let mut updated_files = Vec::new();
+    for entry in files {
         let path = entry.path();
-        if path.is_file() {
-            match update_file_content(path, old_module_name, new_module_name) {
-                Ok(updated) => {
-                    if updated {
-                        println!("Updated module name in: {}", path.display());
-                    }
-                }
-                Err(e) => println!("Error updating file {}: {}", path.display(), e),
+        let relative_path = path.strip_prefix(project_path).unwrap_or(path);
+        let updated = update_file_content(path, old_module_name, new_module_name, dry_run)
+            .map_err(|e| {
+                pb.finish_and_clear();
+                GenesisError::Io(IoError::new(
+                    e.kind(),
+                    format!("Could not update {}: {}", path.display(), e),
+                ))
+            })?;
+        if updated && dry_run {
+            pb.suspend(|| {
+                print_dry_run(format!(
+                    "Would update module name in: {}",
+                    relative_path.display()
+                ))
+            });
+        } else if updated {
+            log::debug!("Updated module name in {}", relative_path.display());
+            updated_files.push(relative_path.display().to_string());
+        }
+        pb.inc(1);
+    }
+    pb.finish_and_clear();
+
+    if !dry_run {
+        say!("Updated module name in {} files", updated_files.len());
+        options.events.emit(Event::FilesRewritten {
+            action: RewriteAction::ModuleName,
+            placeholder: None,
+            files: updated_files,
+        });
+    }
+    Ok(())
+}
+
+/// Replaces `old` with `new` in the given files (relative to the project
+/// root), or in every file outside `.git` when `files` is empty. Returns the
+/// files that contain `old`.
+pub fn replace_in_files(
+    project_path: &Path,
+    files: &[String],
+    old: &str,
+    new: &str,
+    dry_run: bool,
+) -> Result<Vec<PathBuf>, GenesisError> {
+    let mut updated = Vec::new();
+    if !files.is_empty() {
+        for file in files {
+            let path = project_path.join(file);
+            if !path.is_file() {
+                return Err(GenesisError::Template(format!(
+                    "File to patch not found: {}",
+                    path.display()
+                )));
             }
+            if update_file_content(&path, old, new, dry_run)? {
+                updated.push(path);
+            }
+        }
+        return Ok(updated);
+    }
+
+    for entry in WalkDir::new(project_path)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+        .filter_map(|e| e.ok())
+    {
+        if entry.path().is_file() && update_file_content(entry.path(), old, new, dry_run)? {
+            updated.push(entry.into_path());
+        }
+    }
+    Ok(updated)
+}
+
+/// Copies a template's working tree, leaving out `.git` and anything matched
+/// by the gitignore-style patterns in its `.genesisignore` files.
+pub fn copy_template_dir(source: &Path, destination: &Path) -> Result<(), GenesisError> {
+    let walker = WalkBuilder::new(source)
+        .standard_filters(false)
+        .add_custom_ignore_filename(GENESIS_IGNORE_FILE)
+        .filter_entry(|e| e.file_name() != ".git" && e.file_name() != GENESIS_IGNORE_FILE)
+        .build();
+
+    for entry in walker {
+        let entry = entry?;
+        let Ok(relative) = entry.path().strip_prefix(source) else {
+            continue;
+        };
+        let target = destination.join(relative);
+        if entry.file_type().is_some_and(|t| t.is_dir()) {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
         }
     }
     Ok(())
 }
 
+/// Replaces `old_module_name` in a file, returning whether it occurred.
+/// With `dry_run` the file is only checked. An empty `old_module_name`
+/// occurs nowhere.
 fn update_file_content(
     path: &Path,
     old_module_name: &str,
     new_module_name: &str,
+    dry_run: bool,
 ) -> Result<bool, IoError> {
+    if old_module_name.is_empty() {
+        return Ok(false);
+    }
+
     let mut content = Vec::new();
     let mut file = fs::File::open(path)?;
     file.read_to_end(&mut content)?;
@@ -56,10 +165,46 @@ fn update_file_content(
         }
     }
 
-    if updated {
+    if updated && !dry_run {
         let mut file = fs::File::create(path)?;
         file.write_all(&new_content)?;
     }
 
     Ok(updated)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_in_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".git/config"), "acme").unwrap();
+        fs::write(root.join("a.txt"), "acme and acme").unwrap();
+        fs::write(root.join("b.txt"), "nothing").unwrap();
+
+        let cases = [
+            ("", vec![]),
+            ("acme", vec![root.join("a.txt")]),
+            ("acme", vec![]),
+        ];
+        for (old, expected) in cases {
+            let updated = replace_in_files(root, &[], old, "demo", false).unwrap();
+            assert_eq!(updated, expected, "{:?}", old);
+        }
+        assert_eq!(
+            fs::read_to_string(root.join("a.txt")).unwrap(),
+            "demo and demo"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join(".git/config")).unwrap(),
+            "acme"
+        );
+
+        let missing = replace_in_files(root, &["c.txt".to_string()], "x", "y", false);
+        assert!(missing.unwrap_err().to_string().contains("not found"));
+    }
+}
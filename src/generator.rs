@@ -0,0 +1,333 @@
+use console::Term;
+use dialoguer::Input;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::error::GenesisError;
+use crate::event::{Event, Events};
+use crate::git::{initial_commit, reinit_repo};
+use crate::hooks::{run_hooks, HookStage};
+use crate::registry::Template;
+use crate::setup::{
+    check_destination, generate_staged, setup_custom_project, setup_go_project,
+    setup_react_ts_vite_project, setup_rust_project, ExistingDestination, SetupOptions,
+};
+use crate::toolchain::preflight;
+use crate::utils::{
+    print_dry_run, prompt_database_selection, prompt_rust_template, prompt_step,
+    require_interactive,
+};
+use crate::validate::{validate_module_path, validate_project_name};
+
+/// Generates a project from a template. Built up with its setters, e.g.
+///
+/// ```no_run
+/// use genesis_rs::{Generator, GenesisError, Template};
+///
+/// let project = Generator::new(Template::from_source("https://github.com/acme/starter.git"))
+///     .name("my-api")
+///     .destination("/home/me/code")
+///     .var("author", "Me")
+///     .on_event(|event| println!("{:?}", event))
+///     .generate()?;
+/// # Ok::<(), GenesisError>(())
+/// ```
+///
+/// Values a template needs that weren't given are an error, unless the
+/// generator is `interactive`, in which case they're prompted for.
+pub struct Generator {
+    template: Template,
+    name: Option<String>,
+    destination: Option<PathBuf>,
+    options: SetupOptions,
+    module: Option<String>,
+    database: Option<String>,
+    rust_template: Option<String>,
+    keep_history: bool,
+    initial_commit: Option<(String, Option<String>)>,
+    checked: bool,
+}
+
+impl Generator {
+    pub fn new(template: Template) -> Self {
+        Generator {
+            template,
+            name: None,
+            destination: None,
+            options: SetupOptions {
+                run_hooks: true,
+                ..SetupOptions::default()
+            },
+            module: None,
+            database: None,
+            rust_template: None,
+            keep_history: false,
+            initial_commit: None,
+            checked: false,
+        }
+    }
+
+    /// The project's name, which is also the name of its directory.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// The directory the project's directory is created in. Defaults to the
+    /// current directory.
+    pub fn destination(mut self, destination: impl Into<PathBuf>) -> Self {
+        self.destination = Some(destination.into());
+        self
+    }
+
+    /// Sets a variable declared by the template's `genesis.toml`.
+    pub fn var(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.options.vars.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn vars(mut self, vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.options.vars.extend(vars);
+        self
+    }
+
+    /// The module path of a Go project. Templates other than the Go starter
+    /// get it as the `module_path` variable.
+    pub fn module(mut self, module: impl Into<String>) -> Self {
+        self.module = Some(module.into());
+        self
+    }
+
+    /// The database of a Go project, `mongodb` (the default) or
+    /// `postgresql`. Templates other than the Go starter get it as the
+    /// `database` variable.
+    pub fn database(mut self, database: impl Into<String>) -> Self {
+        self.database = Some(database.into());
+        self
+    }
+
+    /// The kind of Rust project, `basic` (the default) or `full`.
+    pub fn rust_template(mut self, rust_template: impl Into<String>) -> Self {
+        self.rust_template = Some(rust_template.into());
+        self
+    }
+
+    /// Whether missing values may be prompted for on the terminal.
+    pub fn interactive(mut self, interactive: bool) -> Self {
+        self.options.interactive = interactive;
+        self
+    }
+
+    /// Reports every change instead of making it.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.options.dry_run = dry_run;
+        self
+    }
+
+    /// Keeps the partial project when generating fails.
+    pub fn keep_on_failure(mut self, keep_on_failure: bool) -> Self {
+        self.options.keep_on_failure = keep_on_failure;
+        self
+    }
+
+    /// What to do when the project's directory already exists.
+    pub fn existing(mut self, existing: ExistingDestination) -> Self {
+        self.options.existing = existing;
+        self
+    }
+
+    /// Whether to run the template's and the user's hooks (see [`Hooks`](crate::Hooks)).
+    /// Turn them off for templates you don't trust.
+    pub fn hooks(mut self, hooks: bool) -> Self {
+        self.options.run_hooks = hooks;
+        self
+    }
+
+    /// Keeps the template's git history instead of starting a fresh
+    /// repository.
+    pub fn keep_history(mut self, keep_history: bool) -> Self {
+        self.keep_history = keep_history;
+        self
+    }
+
+    /// Commits the generated project to its fresh repository, by `author`
+    /// (`Name <email>`) or else the git config's user.
+    pub fn initial_commit(mut self, message: impl Into<String>, author: Option<String>) -> Self {
+        self.initial_commit = Some((message.into(), author));
+        self
+    }
+
+    /// Calls `on_event` with each [`Event`] as the project is generated.
+    pub fn on_event(mut self, on_event: impl Fn(&Event) + Send + Sync + 'static) -> Self {
+        self.options.events = Events::new(Arc::new(on_event));
+        self
+    }
+
+    pub fn template(&self) -> &Template {
+        &self.template
+    }
+
+    /// Where the project will be generated.
+    pub fn project_path(&self) -> Result<PathBuf, GenesisError> {
+        let name = self.name.as_ref().ok_or_else(|| {
+            GenesisError::Validation("missing required value: the project name".to_string())
+        })?;
+        Ok(self.base_path().join(name))
+    }
+
+    fn base_path(&self) -> PathBuf {
+        self.destination
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
+
+    /// Makes sure the project can be generated, before anything is written:
+    /// that its name is valid, its directory can be written to and the
+    /// tools the template needs are installed. Done by `generate` when it
+    /// hasn't been already.
+    pub fn check(&mut self) -> Result<(), GenesisError> {
+        let template = &self.template;
+        self.options.events.emit(Event::TemplateResolved {
+            name: template.name.clone(),
+            language: template.language.clone(),
+            url: template.url.clone(),
+            git_ref: template.git_ref.clone(),
+            path: template.subdir.clone(),
+        });
+
+        let project_path = self.project_path()?;
+        let name = self.name.as_deref().unwrap_or_default();
+        validate_project_name(name, &template.language).map_err(GenesisError::Validation)?;
+        if let Some(module) = &self.module {
+            validate_module_path(module).map_err(GenesisError::Validation)?;
+        }
+        // Templates given with `--template` could be in any language.
+        if !matches!(template.language.as_str(), "go" | "custom") {
+            let flag = match (&self.module, &self.database) {
+                (Some(_), _) => Some("--module"),
+                (None, Some(_)) => Some("--database"),
+                (None, None) => None,
+            };
+            if let Some(flag) = flag {
+                return Err(GenesisError::Validation(format!(
+                    "{} only applies to Go templates, and {} is a {} template",
+                    flag,
+                    template.title(),
+                    template.language
+                )));
+            }
+        }
+        check_destination(&project_path, self.options.existing)?;
+        preflight(template, self.options.dry_run)?;
+        self.checked = true;
+        Ok(())
+    }
+
+    /// Generates the project and returns its path. Unless it's a dry run,
+    /// the project is generated in a staging directory and only moved into
+    /// place once every step has succeeded.
+    pub fn generate(mut self) -> Result<PathBuf, GenesisError> {
+        if !self.checked {
+            self.check()?;
+        }
+        self.options.destination = self.project_path()?;
+        if self.template.name != "go" {
+            let go_vars = [("module_path", &self.module), ("database", &self.database)];
+            for (name, value) in go_vars {
+                if let Some(value) = value {
+                    self.options
+                        .vars
+                        .entry(name.to_string())
+                        .or_insert_with(|| value.clone());
+                }
+            }
+        }
+
+        let base_path = self.base_path();
+        if self.options.dry_run {
+            self.generate_into(&base_path)?;
+            if !self.keep_history {
+                print_dry_run("Would replace the template's git history with a new repository");
+                if let Some((message, _)) = &self.initial_commit {
+                    print_dry_run(format!("Would commit every file: \"{}\"", message));
+                }
+            }
+        } else {
+            let name = self.name.as_deref().unwrap_or_default();
+            generate_staged(&base_path, name, &self.options, |staging| {
+                self.generate_into(staging)
+            })?;
+        }
+        self.project_path()
+    }
+
+    /// Writes the project into `base_path`: a staging directory, or the
+    /// real destination for a dry run, which doesn't write to it anyway.
+    fn generate_into(&self, base_path: &Path) -> Result<(), GenesisError> {
+        let term = Term::stdout();
+        let interactive = self.options.interactive;
+        let name = self.name.as_deref().unwrap_or_default();
+        let template = &self.template;
+
+        run_hooks(
+            HookStage::PreClone,
+            &template.hooks,
+            &base_path.join(name),
+            &self.options.vars,
+            &self.options,
+        )?;
+
+        match template.name.as_str() {
+            "go" => {
+                let module_name = match &self.module {
+                    Some(module) => module.clone(),
+                    None => {
+                        require_interactive(interactive, "module")?;
+                        prompt_step(&term, "Enter your Go module name:", || {
+                            Ok(Input::<String>::new()
+                                .with_prompt("Go module name (e.g., github.com/username/project)")
+                                .validate_with(|module: &String| validate_module_path(module))
+                                .interact_text()?)
+                        })?
+                    }
+                };
+                let database = match &self.database {
+                    Some(database) => database.clone(),
+                    None if !interactive => "mongodb".to_string(),
+                    None => prompt_database_selection(&term)?,
+                };
+
+                setup_go_project(
+                    base_path,
+                    name,
+                    &module_name,
+                    &database,
+                    template,
+                    &self.options,
+                )?
+            }
+            "rust" => {
+                let rust_template = match &self.rust_template {
+                    Some(rust_template) => rust_template.clone(),
+                    None if !interactive => "basic".to_string(),
+                    None => prompt_rust_template(&term)?,
+                };
+
+                setup_rust_project(base_path, name, &rust_template, template, &self.options)?
+            }
+            "react" => setup_react_ts_vite_project(base_path, name, template, &self.options)?,
+            _ => setup_custom_project(base_path, name, template, &self.options)?,
+        }
+
+        if !self.options.dry_run && !self.keep_history {
+            let repo = reinit_repo(&base_path.join(name))?;
+            if let Some((message, author)) = &self.initial_commit {
+                initial_commit(&repo, message, author.as_deref())?;
+            }
+        }
+
+        Ok(())
+    }
+}
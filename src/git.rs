@@ -1,57 +1,337 @@
-use git2::{build::RepoBuilder, Cred, FetchOptions, Progress, RemoteCallbacks, Repository};
-use indicatif::{ProgressBar, ProgressStyle};
-use std::{
-    cell::RefCell,
-    path::Path,
-    rc::Rc,
+use git2::{
+    build::{CheckoutBuilder, RepoBuilder},
+    BranchType, Direction, FetchOptions, IndexAddOption, Oid, Progress, Remote, RemoteCallbacks,
+    Repository, Signature,
 };
+use indicatif::ProgressBar;
+use std::{fs, path::Path};
 
-pub fn clone_repo(url: &str, path: &str) -> Result<Repository, git2::Error> {
-    let token = "";
-    let pb = Rc::new(RefCell::new(ProgressBar::new(100)));
-    pb.borrow_mut().set_style(
-        ProgressStyle::default_bar()
-            .template(
-                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos:>7}/{len:7} {msg}",
-            )
-            .progress_chars("#>-"),
+use crate::auth::Authenticator;
+use crate::cache::mirror_path;
+use crate::error::GenesisError;
+use crate::progress;
+
+/// Clones `url` into `path`, checking out `git_ref` (a branch, tag or
+/// commit) when given and the remote's default branch otherwise.
+///
+/// The clone is made from the template's mirror in the local cache, which
+/// is brought up to date first. When that fails, e.g. because the network
+/// is down, the cached copy is used as it is.
+///
+/// With `shallow`, only the tip of each branch and tag is fetched. A `git_ref`
+/// naming an older commit then fetches the full history on demand.
+///
+/// `token` authenticates HTTPS remotes; see `auth::Authenticator` for the
+/// other credentials that are tried.
+pub fn clone_repo(
+    url: &str,
+    path: &Path,
+    git_ref: Option<&str>,
+    shallow: bool,
+    token: Option<&str>,
+) -> Result<Repository, git2::Error> {
+    // libgit2's local transport can't fetch shallowly.
+    let shallow = shallow && !is_local(url);
+
+    let mut repo = clone_from_mirror(url, path, shallow, token)?;
+    if let Some(git_ref) = git_ref {
+        if let Err(e) = checkout_ref(&repo, git_ref) {
+            if !shallow {
+                return Err(e);
+            }
+            drop(repo);
+            fs::remove_dir_all(path).map_err(|e| git2::Error::from_str(&e.to_string()))?;
+            repo = clone_from_mirror(url, path, false, token)?;
+            checkout_ref(&repo, git_ref)?;
+        }
+    }
+    Ok(repo)
+}
+
+fn clone_from_mirror(
+    url: &str,
+    path: &Path,
+    shallow: bool,
+    token: Option<&str>,
+) -> Result<Repository, git2::Error> {
+    let mirror = mirror_path(url);
+    let source = match &mirror {
+        Some(mirror) => match update_mirror(url, mirror, shallow, token) {
+            Ok(()) => mirror.to_string_lossy().into_owned(),
+            Err(e) if mirror.exists() => {
+                log::warn!(
+                    "Could not update template ({}), using the cached copy",
+                    e.message()
+                );
+                mirror.to_string_lossy().into_owned()
+            }
+            Err(e) => return Err(e),
+        },
+        None => url.to_string(),
+    };
+    log::info!("Cloning {} into {}", source, path.display());
+
+    let pb = progress::bar(0, "Cloning template...");
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(remote_callbacks(&pb, token));
+
+    let mut builder = RepoBuilder::new();
+    builder.fetch_options(fetch_options);
+
+    let result = builder.clone(&source, path);
+
+    pb.finish_and_clear();
+
+    let repo = result?;
+    repo.remote_set_url("origin", url)?;
+
+    // A clone of a shallow mirror is missing the same history, so it has to
+    // know where its history is cut off too.
+    if let Some(shallow_file) = mirror.map(|m| m.join("shallow")).filter(|f| f.exists()) {
+        fs::copy(shallow_file, repo.path().join("shallow"))
+            .map_err(|e| git2::Error::from_str(&e.to_string()))?;
+    }
+    Ok(repo)
+}
+
+fn is_local(url: &str) -> bool {
+    url.starts_with("file://") || Path::new(url).exists()
+}
+
+/// Creates or incrementally updates the bare mirror of `url` at `mirror`.
+/// A `shallow` update fetches only the tip of each branch and tag, while a
+/// full one also fills in the history of a previously shallow mirror.
+pub fn update_mirror(
+    url: &str,
+    mirror: &Path,
+    shallow: bool,
+    token: Option<&str>,
+) -> Result<(), git2::Error> {
+    let created = !mirror.exists();
+    let shallow = shallow && !is_local(url);
+    let mut result = fetch_mirror(url, mirror, shallow, token);
+    if shallow && matches!(&result, Err(e) if shallow_unsupported(e)) {
+        // Not every server handles libgit2's shallow requests.
+        result = fetch_mirror(url, mirror, false, token);
+    }
+    if result.is_err() && created {
+        // Don't leave an empty mirror behind to be mistaken for a cached copy.
+        let _ = fs::remove_dir_all(mirror);
+    }
+    result
+}
+
+/// Whether `error` means the server refused the depth limit, rather than
+/// the fetch failing for a reason a full fetch would hit too (an unreachable
+/// host, bad credentials).
+fn shallow_unsupported(error: &git2::Error) -> bool {
+    let message = error.message();
+    message.contains("shallow") || message.contains("deepen")
+}
+
+fn fetch_mirror(
+    url: &str,
+    mirror: &Path,
+    shallow: bool,
+    token: Option<&str>,
+) -> Result<(), git2::Error> {
+    let repo = if mirror.exists() {
+        Repository::open_bare(mirror)?
+    } else {
+        let repo = Repository::init_bare(mirror)?;
+        repo.remote_with_fetch("origin", url, "+refs/heads/*:refs/heads/*")?;
+        repo
+    };
+
+    let pb = progress::bar(0, format!("Fetching {}...", url));
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(remote_callbacks(&pb, token));
+    if shallow {
+        fetch_options.depth(1);
+    } else if repo.is_shallow() {
+        fetch_options.depth(i32::MAX);
+    }
+
+    let mut remote = repo.find_remote("origin")?;
+    let result = remote.fetch(
+        &["+refs/heads/*:refs/heads/*", "+refs/tags/*:refs/tags/*"],
+        Some(&mut fetch_options),
+        None,
     );
+    pb.finish_and_clear();
+    result?;
 
-    let pb_clone = pb.clone();
+    // Clones made from the mirror check out whatever its HEAD points at.
+    if let Ok(default_branch) = remote.default_branch() {
+        if let Some(default_branch) = default_branch.as_str() {
+            repo.set_head(default_branch)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reports the objects received and the deltas resolved on `pb`, and
+/// authenticates with `auth::Authenticator`.
+fn remote_callbacks(pb: &ProgressBar, token: Option<&str>) -> RemoteCallbacks<'static> {
+    let pb = pb.clone();
 
+    let mut authenticator = Authenticator::new(token);
     let mut callbacks = RemoteCallbacks::new();
-    callbacks.credentials(move |_url, _username_from_url, _allowed_types| {
-        Cred::userpass_plaintext("git", &token)
+    callbacks.credentials(move |url, username_from_url, allowed_types| {
+        authenticator.credentials(url, username_from_url, allowed_types)
     });
 
     callbacks.transfer_progress(move |stats: Progress| {
-        let pb = pb_clone.borrow_mut();
-        if stats.received_objects() == stats.total_objects() {
-            pb.set_message("Resolving deltas...");
-        } else if stats.total_objects() > 0 {
+        if stats.total_objects() == 0 {
+            return true;
+        }
+        if stats.received_objects() < stats.total_objects() {
             pb.set_message("Receiving objects...");
+            pb.set_length(stats.total_objects() as u64);
+            pb.set_position(stats.received_objects() as u64);
         } else {
-            pb.set_message("Preparing...");
+            pb.set_message("Resolving deltas...");
+            pb.set_length(stats.total_deltas() as u64);
+            pb.set_position(stats.indexed_deltas() as u64);
         }
-
-        let progress = if stats.total_objects() > 0 {
-            (100 * stats.received_objects() / stats.total_objects()) as u64
-        } else {
-            0
-        };
-        pb.set_position(progress);
         true
     });
 
-    let mut fetch_options = FetchOptions::new();
-    fetch_options.remote_callbacks(callbacks);
+    callbacks
+}
 
-    let mut builder = RepoBuilder::new();
-    builder.fetch_options(fetch_options);
+/// Connects to `url` and lists its refs without fetching anything, to tell
+/// whether a template source can be reached with the available credentials.
+pub fn check_remote(url: &str, token: Option<&str>) -> Result<(), git2::Error> {
+    let mut remote = Remote::create_detached(url)?;
+    let mut authenticator = Authenticator::new(token);
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username_from_url, allowed_types| {
+        authenticator.credentials(url, username_from_url, allowed_types)
+    });
 
-    let result = builder.clone(url, Path::new(path));
+    let connection = remote.connect_auth(Direction::Fetch, Some(callbacks), None)?;
+    connection.list()?;
+    Ok(())
+}
 
-    pb.borrow_mut().finish_with_message("Done!");
+fn checkout_ref(repo: &Repository, git_ref: &str) -> Result<(), git2::Error> {
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force();
 
-    result
+    if let Ok(remote_branch) = repo.find_branch(&format!("origin/{}", git_ref), BranchType::Remote)
+    {
+        let commit = remote_branch.get().peel_to_commit()?;
+        if repo.find_branch(git_ref, BranchType::Local).is_err() {
+            repo.branch(git_ref, &commit, false)?;
+        }
+        repo.checkout_tree(commit.as_object(), Some(&mut checkout))?;
+        return repo.set_head(&format!("refs/heads/{}", git_ref));
+    }
+
+    let commit = repo
+        .revparse_single(git_ref)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|_| {
+            git2::Error::from_str(&format!(
+                "Template ref '{}' is not a branch, tag or commit",
+                git_ref
+            ))
+        })?;
+    repo.checkout_tree(commit.as_object(), Some(&mut checkout))?;
+    repo.set_head_detached(commit.id())
+}
+
+/// Replaces the template's history, and with it the template's `origin`
+/// remote, with a freshly initialized repository.
+pub fn reinit_repo(project_path: &Path) -> Result<Repository, GenesisError> {
+    let git_dir = project_path.join(".git");
+    if git_dir.exists() {
+        fs::remove_dir_all(&git_dir)?;
+    }
+    Repository::init(project_path).map_err(GenesisError::Git)
+}
+
+/// Commits every file in the project. `author` has the form
+/// `Name <email>`; the user's git config is used when it's not given.
+pub fn initial_commit(
+    repo: &Repository,
+    message: &str,
+    author: Option<&str>,
+) -> Result<Oid, GenesisError> {
+    let signature = match author {
+        Some(author) => parse_signature(author)?,
+        None => repo.signature().map_err(|_| {
+            GenesisError::Validation(
+                "No git author configured: pass `--commit-author \"Name <email>\"` or set user.name and user.email"
+                    .to_string(),
+            )
+        })?,
+    };
+    commit_all(repo, message, &signature).map_err(GenesisError::Git)
+}
+
+fn commit_all(repo: &Repository, message: &str, signature: &Signature) -> Result<Oid, git2::Error> {
+    let mut index = repo.index()?;
+    index.add_all(["*"].iter(), IndexAddOption::DEFAULT, None)?;
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+
+    repo.commit(Some("HEAD"), signature, signature, message, &tree, &[])
+}
+
+fn parse_signature(author: &str) -> Result<Signature<'static>, GenesisError> {
+    let invalid = || {
+        GenesisError::Validation(format!(
+            "Invalid commit author '{}', expected \"Name <email>\"",
+            author
+        ))
+    };
+    let (name, email) = author.split_once('<').ok_or_else(invalid)?;
+    let email = email.strip_suffix('>').ok_or_else(invalid)?;
+    Signature::now(name.trim(), email.trim()).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commit_authors() {
+        let cases = [
+            ("Me <me@example.com>", Some(("Me", "me@example.com"))),
+            (
+                "Jane Doe <jane@example.com>",
+                Some(("Jane Doe", "jane@example.com")),
+            ),
+            ("me@example.com", None),
+            ("Me <me@example.com", None),
+            ("<me@example.com>", None),
+        ];
+        for (author, expected) in cases {
+            let signature = parse_signature(author).ok();
+            let parsed = signature
+                .as_ref()
+                .map(|s| (s.name().unwrap(), s.email().unwrap()));
+            assert_eq!(parsed, expected, "{}", author);
+        }
+    }
+
+    #[test]
+    fn retries_only_refused_shallow_fetches() {
+        let cases = [
+            ("server doesn't support shallow", true),
+            ("remote error: deepen not allowed", true),
+            ("failed to resolve address for example.invalid", false),
+            (
+                "failed to connect to example.com: Connection refused",
+                false,
+            ),
+            ("authentication required but no callback set", false),
+        ];
+        for (message, expected) in cases {
+            let error = git2::Error::from_str(message);
+            assert_eq!(shallow_unsupported(&error), expected, "{}", message);
+        }
+    }
 }
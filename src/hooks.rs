@@ -0,0 +1,292 @@
+use console::style;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, path::Path};
+
+use crate::error::GenesisError;
+use crate::event::Event;
+use crate::output::say;
+use crate::setup::{run_streamed, shell_command, SetupOptions};
+use crate::utils::print_dry_run;
+
+/// When in the generation of a project a hook runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookStage {
+    /// Before the template is cloned, in the directory the project is
+    /// created in.
+    PreClone,
+    /// Once the template has been cloned, before anything in it changes.
+    PostClone,
+    /// Once the template's placeholders have been filled in.
+    PostRender,
+    /// Once the template's setup commands (e.g. `npm install`) have run.
+    PostInstall,
+}
+
+impl fmt::Display for HookStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HookStage::PreClone => "pre-clone",
+            HookStage::PostClone => "post-clone",
+            HookStage::PostRender => "post-render",
+            HookStage::PostInstall => "post-install",
+        })
+    }
+}
+
+/// Shell commands to run at each stage, e.g.
+/// `post-render = ["./scripts/setup.sh"]`. Declared under `[hooks]` in a
+/// template's `genesis.toml`, and in the registry file for every template
+/// or a single one.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Hooks {
+    #[serde(default)]
+    pub pre_clone: Vec<String>,
+    #[serde(default)]
+    pub post_clone: Vec<String>,
+    #[serde(default)]
+    pub post_render: Vec<String>,
+    #[serde(default)]
+    pub post_install: Vec<String>,
+}
+
+impl Hooks {
+    pub fn commands(&self, stage: HookStage) -> &[String] {
+        match stage {
+            HookStage::PreClone => &self.pre_clone,
+            HookStage::PostClone => &self.post_clone,
+            HookStage::PostRender => &self.post_render,
+            HookStage::PostInstall => &self.post_install,
+        }
+    }
+
+    /// Adds `other`'s hooks after these, stage by stage.
+    pub fn append(&mut self, other: &Hooks) {
+        self.pre_clone.extend_from_slice(&other.pre_clone);
+        self.post_clone.extend_from_slice(&other.post_clone);
+        self.post_render.extend_from_slice(&other.post_render);
+        self.post_install.extend_from_slice(&other.post_install);
+    }
+}
+
+/// Runs the hooks for `stage` one after the other through the shell, and
+/// stops at the first that fails. They run in `project_path`, where the
+/// project is being generated, or for pre-clone hooks the directory the
+/// project will be created in, with the project and the template's
+/// variables in their environment:
+///
+/// - `GENESIS_HOOK`: the stage, e.g. `post-render`
+/// - `GENESIS_PROJECT_NAME`
+/// - `GENESIS_PROJECT_PATH`: where the project ends up, rather than the
+///   staging directory it's generated in
+/// - `GENESIS_VAR_<NAME>` for each variable, e.g. `GENESIS_VAR_MODULE_PATH`
+pub(crate) fn run_hooks(
+    stage: HookStage,
+    hooks: &Hooks,
+    project_path: &Path,
+    vars: &HashMap<String, String>,
+    options: &SetupOptions,
+) -> Result<(), GenesisError> {
+    let commands = hooks.commands(stage);
+    if commands.is_empty() {
+        return Ok(());
+    }
+    if !options.run_hooks {
+        log::info!("Skipping {} {} hooks", commands.len(), stage);
+        return Ok(());
+    }
+
+    let destination = &options.destination;
+    let dir = match stage {
+        HookStage::PreClone => destination.parent().unwrap_or(Path::new(".")),
+        _ => project_path,
+    };
+    for command in commands {
+        if options.dry_run {
+            print_dry_run(format!("Would run the {} hook: {}", stage, command));
+            continue;
+        }
+
+        say!(
+            "{} {}",
+            style(format!("Running {} hook:", stage)).cyan(),
+            command
+        );
+        log::info!(
+            "Running the {} hook `{}` in {}",
+            stage,
+            command,
+            dir.display()
+        );
+        let (mut process, shell) = shell_command(command);
+        process
+            .current_dir(dir)
+            .env("GENESIS_HOOK", stage.to_string())
+            .env(
+                "GENESIS_PROJECT_NAME",
+                destination.file_name().unwrap_or_default(),
+            )
+            .env("GENESIS_PROJECT_PATH", destination);
+        for (name, value) in vars {
+            process.env(env_name(name), value);
+        }
+
+        let status = run_streamed(&mut process, shell, command)?;
+        options.events.emit(Event::Hook {
+            hook: stage,
+            command: command.clone(),
+            exit_code: status.code(),
+        });
+        if !status.success() {
+            return Err(GenesisError::HookFailed {
+                hook: stage.to_string(),
+                command: command.clone(),
+                status,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// `GENESIS_VAR_` followed by the variable's name in upper case, with
+/// anything but letters and digits replaced by `_`.
+fn env_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect();
+    format!("GENESIS_VAR_{}", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn names_variables() {
+        let cases = [
+            ("module_path", "GENESIS_VAR_MODULE_PATH"),
+            ("database", "GENESIS_VAR_DATABASE"),
+            ("app-name", "GENESIS_VAR_APP_NAME"),
+            ("v2.api", "GENESIS_VAR_V2_API"),
+            ("naïve", "GENESIS_VAR_NA_VE"),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(env_name(name), expected);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runs_hooks_with_the_project_in_their_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let staging = dir.path().join(".staging/demo");
+        fs::create_dir_all(&staging).unwrap();
+        let options = SetupOptions {
+            run_hooks: true,
+            destination: dir.path().join("demo"),
+            ..SetupOptions::default()
+        };
+        let hooks = Hooks {
+            pre_clone: vec!["pwd > pre-clone.txt".to_string()],
+            post_render: vec![
+                "echo \"$GENESIS_HOOK $GENESIS_PROJECT_NAME $GENESIS_PROJECT_PATH\" > env.txt"
+                    .to_string(),
+                "echo \"$GENESIS_VAR_MODULE_PATH\" >> env.txt".to_string(),
+            ],
+            ..Hooks::default()
+        };
+        let vars = HashMap::from([("module_path".to_string(), "example.com/demo".to_string())]);
+
+        run_hooks(HookStage::PreClone, &hooks, &staging, &vars, &options).unwrap();
+        run_hooks(HookStage::PostRender, &hooks, &staging, &vars, &options).unwrap();
+
+        let pre_clone = fs::read_to_string(dir.path().join("pre-clone.txt")).unwrap();
+        assert_eq!(
+            Path::new(pre_clone.trim()).canonicalize().unwrap(),
+            dir.path().canonicalize().unwrap()
+        );
+        assert_eq!(
+            fs::read_to_string(staging.join("env.txt")).unwrap(),
+            format!(
+                "post-render demo {}\nexample.com/demo\n",
+                dir.path().join("demo").display()
+            )
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stops_at_the_first_failing_hook() {
+        let dir = tempfile::tempdir().unwrap();
+        let hooks = Hooks {
+            post_install: vec![
+                "touch first".to_string(),
+                "exit 3".to_string(),
+                "touch third".to_string(),
+            ],
+            ..Hooks::default()
+        };
+        let options = SetupOptions {
+            run_hooks: true,
+            destination: dir.path().to_path_buf(),
+            ..SetupOptions::default()
+        };
+
+        let error = run_hooks(
+            HookStage::PostInstall,
+            &hooks,
+            dir.path(),
+            &HashMap::new(),
+            &options,
+        )
+        .unwrap_err();
+        match error {
+            GenesisError::HookFailed {
+                hook,
+                command,
+                status,
+            } => {
+                assert_eq!(hook, "post-install");
+                assert_eq!(command, "exit 3");
+                assert_eq!(status.code(), Some(3));
+            }
+            e => panic!("unexpected {:?}", e),
+        }
+        assert!(dir.path().join("first").exists());
+        assert!(!dir.path().join("third").exists());
+    }
+
+    #[test]
+    fn skips_hooks_when_asked_or_dry_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let hooks = Hooks {
+            post_clone: vec!["touch ran".to_string()],
+            ..Hooks::default()
+        };
+        let cases = [
+            SetupOptions::default(),
+            SetupOptions {
+                run_hooks: true,
+                dry_run: true,
+                ..SetupOptions::default()
+            },
+        ];
+        for options in cases {
+            run_hooks(
+                HookStage::PostClone,
+                &hooks,
+                dir.path(),
+                &HashMap::new(),
+                &options,
+            )
+            .unwrap();
+            assert!(!dir.path().join("ran").exists());
+        }
+    }
+}
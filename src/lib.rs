@@ -0,0 +1,36 @@
+//! Genesis sets up starter projects from templates: it clones the template,
+//! fills in its variables, runs its setup commands and gives the project a
+//! fresh git repository.
+//!
+//! The `genesis` command line tool is a front end to [`Generator`], which
+//! can be used to generate projects from other tools as well. Events
+//! reporting its progress can be received with [`Generator::on_event`].
+//! Nothing is printed unless [`output::set_format`] is called.
+
+mod auth;
+pub mod cache;
+mod constants;
+pub mod doctor;
+pub mod error;
+pub mod event;
+mod file;
+mod generator;
+mod git;
+mod hooks;
+pub mod logging;
+mod manifest;
+pub mod output;
+mod progress;
+pub mod registry;
+mod render;
+mod setup;
+mod toolchain;
+pub mod utils;
+pub mod validate;
+
+pub use error::GenesisError;
+pub use event::{Event, RewriteAction};
+pub use generator::Generator;
+pub use hooks::{HookStage, Hooks};
+pub use registry::{Registry, Template};
+pub use setup::{remove_staging_dirs, update_genesis, ExistingDestination};
@@ -0,0 +1,153 @@
+use console::style;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::{Mutex, OnceLock},
+    time::Instant,
+};
+
+use crate::error::GenesisError;
+use crate::progress;
+
+/// How much genesis prints, from `-q` to `-vv`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only errors.
+    Quiet,
+    /// The usual progress messages, plus warnings.
+    Normal,
+    /// Also what genesis does behind each step: clones, commands, files.
+    Verbose,
+    /// Also every file touched and every check made.
+    Debug,
+}
+
+impl Verbosity {
+    /// The verbosity for `-q` and the number of `-v` flags given.
+    pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Debug,
+        }
+    }
+
+    fn level(self) -> LevelFilter {
+        match self {
+            Verbosity::Quiet => LevelFilter::Error,
+            Verbosity::Normal => LevelFilter::Warn,
+            Verbosity::Verbose => LevelFilter::Info,
+            Verbosity::Debug => LevelFilter::Debug,
+        }
+    }
+}
+
+/// Writes log records to stderr, up to the level `-q`/`-v` ask for, and
+/// everything down to debug to the log file when there is one.
+struct Logger {
+    level: LevelFilter,
+    file: Option<Mutex<File>>,
+    started: Instant,
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+static VERBOSITY: OnceLock<Verbosity> = OnceLock::new();
+
+/// Sets up logging for the rest of the run. With a `log_file`, the file is
+/// appended to and also gets every message genesis prints, so a failed run
+/// can be looked into after the fact.
+pub fn init(verbosity: Verbosity, log_file: Option<&Path>) -> Result<(), GenesisError> {
+    let file = log_file
+        .map(|path| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| {
+                    GenesisError::Validation(format!(
+                        "Could not open log file {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })
+        })
+        .transpose()?;
+
+    let level = verbosity.level();
+    let max_level = match file {
+        Some(_) => level.max(LevelFilter::Debug),
+        None => level,
+    };
+    let _ = VERBOSITY.set(verbosity);
+    let logger = LOGGER.get_or_init(|| Logger {
+        level,
+        file: file.map(Mutex::new),
+        started: Instant::now(),
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(max_level);
+    }
+    logger.write_file("START", &std::env::args().collect::<Vec<_>>().join(" "));
+    Ok(())
+}
+
+pub fn verbosity() -> Verbosity {
+    VERBOSITY.get().copied().unwrap_or(Verbosity::Normal)
+}
+
+/// Adds a message that was printed for people to the log file, if any.
+pub fn to_file(message: &str) {
+    if let Some(logger) = LOGGER.get() {
+        logger.write_file("OUTPUT", message);
+    }
+}
+
+impl Logger {
+    fn write_file(&self, label: &str, message: &str) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let elapsed = self.started.elapsed().as_secs_f64();
+        if let Ok(mut file) = file.lock() {
+            let message = console::strip_ansi_codes(message);
+            for line in message.lines().filter(|line| !line.trim().is_empty()) {
+                let _ = writeln!(file, "[{:>9.3}s {:<6}] {}", elapsed, label, line);
+            }
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Dependencies log too (e.g. `ignore` for every gitignore it reads);
+        // only genesis' own records are of interest.
+        metadata.target().split("::").next() == module_path!().split("::").next()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = record.args().to_string();
+        if record.level() <= self.level {
+            let label = match record.level() {
+                Level::Error => style("error:").red().bold(),
+                Level::Warn => style("warning:").yellow().bold(),
+                Level::Info => style("info:").cyan(),
+                Level::Debug | Level::Trace => style("debug:").dim(),
+            };
+            progress::suspend(|| eprintln!("{} {}", label, message));
+        }
+        self.write_file(record.level().as_str(), &message);
+    }
+
+    fn flush(&self) {
+        if let Some(Ok(mut file)) = self.file.as_ref().map(|f| f.lock()) {
+            let _ = file.flush();
+        }
+    }
+}
@@ -1,192 +1,477 @@
+use std::collections::HashMap;
+use std::io::{self, IsTerminal};
 use std::path::PathBuf;
-use std::thread;
-use std::time::Duration;
+use std::process::ExitCode;
 
-mod constants;
-mod file;
-mod git;
-mod setup;
-mod utils;
+use genesis_rs::cache::{clean_cache, list_cache, update_cache};
+use genesis_rs::doctor::run_doctor;
+use genesis_rs::logging::{self, Verbosity};
+use genesis_rs::output::{self, say, OutputFormat};
+use genesis_rs::utils::{
+    default_project_path, expand_path, parse_var, print_banner, prompt_step, require_interactive,
+};
+use genesis_rs::validate::{validate_module_path, validate_project_name};
+use genesis_rs::{
+    remove_staging_dirs, update_genesis, ExistingDestination, Generator, GenesisError, Registry,
+    Template,
+};
 
-use crate::setup::{setup_go_project, setup_rust_project, update_genesis};
-use crate::utils::{print_banner, prompt_database_selection, prompt_step};
-
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, Command};
 use colored::*;
 use console::{style, Term};
 use dialoguer::{theme::ColorfulTheme, Input, Select};
-use dirs::desktop_dir;
 use dotenv::dotenv;
-use indicatif::{ProgressBar, ProgressStyle};
-use setup::setup_react_ts_vite_project;
+use serde_json::json;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    dotenv().ok();
-    let term = Term::stdout();
-    print_banner();
+fn main() -> ExitCode {
+    // A run interrupted during a setup command, such as `npm install`,
+    // would otherwise leave its staging directory behind.
+    let _ = ctrlc::set_handler(|| {
+        remove_staging_dirs();
+        let _ = Term::stderr().show_cursor();
+        std::process::exit(130);
+    });
 
-    let default_path = desktop_dir()
-        .expect("could not find desktop directory")
-        .to_str()
-        .expect("Desktop path is not valid UTF-8")
-        .to_string();
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if output::is_json() => {
+            output::emit(
+                "summary",
+                json!({
+                    "success": false,
+                    "error": e.to_string(),
+                    "kind": e.kind(),
+                    "exit_code": e.exit_code(),
+                }),
+            );
+            ExitCode::from(e.exit_code())
+        }
+        Err(e) => {
+            logging::to_file(&format!("Error: {}", e));
+            eprintln!("{} {}", style("Error:").red().bold(), e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
 
-    let spinner_style = ProgressStyle::default_spinner()
-        .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ")
-        .template("{spinner:.green} {msg}");
+fn run() -> Result<(), GenesisError> {
+    dotenv().ok();
+    let term = Term::stdout();
 
-    let progress_style = ProgressStyle::default_bar()
-        .template(
-            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos:>7}/{len:7} {msg}",
-        )
-        .progress_chars("#>-");
+    let default_path = default_project_path()?.display().to_string();
 
     let matches = Command::new("Genesis")
         .version("1.2.0")
         .author("Thembinkosi Mkhonta")
         .about("Sets up starter projects for Go and Rust")
         .subcommand(Command::new("update").about("Updates genesis to the latest version"))
+        .subcommand(
+            Command::new("doctor")
+                .about("Reports toolchains, template sources and configuration, for troubleshooting"),
+        )
+        .subcommand(
+            Command::new("cache")
+                .about("Manages the local cache of templates")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("Lists the cached templates"))
+                .subcommand(
+                    Command::new("update")
+                        .about("Caches every registry template and updates cached ones"),
+                )
+                .subcommand(Command::new("clean").about("Removes all cached templates")),
+        )
         .arg(
             Arg::new("language")
                 .short('l')
                 .long("language")
                 .value_name("LANGUAGE")
-                .help("Sets the project language (go, rust, or react)"),
-        )
-        // .arg(
-        //     Arg::new("path")
-        //         .short('p')
-        //         .long("path")
-        //         .value_name("PATH")
-        //         .help("Sets the path where the project will be cloned")
-        //         .default_value(&*default_path.clone()),
-        // )
+                .help("Sets the project language (go, rust, or react) or a template name from the registry"),
+        )
+        .arg(
+            Arg::new("template")
+                .short('t')
+                .long("template")
+                .value_name("SOURCE")
+                .conflicts_with("language")
+                .help("Uses a template from a git URL, a file:// URL or a local directory"),
+        )
+        .arg(
+            Arg::new("name")
+                .short('n')
+                .long("name")
+                .value_name("NAME")
+                .help("Sets the project name"),
+        )
+        .arg(
+            Arg::new("path")
+                .short('p')
+                .long("path")
+                .value_name("PATH")
+                .help("Sets the path where the project will be cloned"),
+        )
+        .arg(
+            Arg::new("module")
+                .short('m')
+                .long("module")
+                .value_name("MODULE")
+                .value_parser(|module: &str| validate_module_path(module).map(|_| module.to_string()))
+                .help("Sets the Go module name (e.g., github.com/username/project)"),
+        )
+        .arg(
+            Arg::new("database")
+                .short('d')
+                .long("database")
+                .value_name("DATABASE")
+                .value_parser(["mongodb", "postgresql"])
+                .help("Sets the database for Go projects"),
+        )
+        .arg(
+            Arg::new("rust-template")
+                .long("rust-template")
+                .value_name("TEMPLATE")
+                .value_parser(["basic", "full"])
+                .help("Sets the Rust project type"),
+        )
+        .arg(
+            Arg::new("var")
+                .long("var")
+                .value_name("KEY=VALUE")
+                .action(ArgAction::Append)
+                .help("Sets a variable declared by the template's genesis.toml"),
+        )
+        .arg(
+            Arg::new("ref")
+                .long("ref")
+                .value_name("REF")
+                .help("Pins the template to a branch, tag or commit"),
+        )
+        .arg(
+            Arg::new("full-history")
+                .long("full-history")
+                .action(ArgAction::SetTrue)
+                .help("Fetches the template's full history instead of only its latest commit"),
+        )
+        .arg(
+            Arg::new("keep-history")
+                .long("keep-history")
+                .action(ArgAction::SetTrue)
+                .help("Keeps the template's git history instead of starting a fresh repository"),
+        )
+        .arg(
+            Arg::new("initial-commit")
+                .long("initial-commit")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("keep-history")
+                .help("Commits the generated project to its fresh repository"),
+        )
+        .arg(
+            Arg::new("commit-message")
+                .long("commit-message")
+                .value_name("MESSAGE")
+                .default_value("Initial commit")
+                .help("Sets the message of the initial commit"),
+        )
+        .arg(
+            Arg::new("commit-author")
+                .long("commit-author")
+                .value_name("NAME <EMAIL>")
+                .help("Sets the author of the initial commit (defaults to your git config)"),
+        )
+        .arg(
+            Arg::new("yes")
+                .short('y')
+                .long("yes")
+                .action(ArgAction::SetTrue)
+                .help("Skips all prompts, using defaults for values not given as flags"),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("merge")
+                .help("Overwrites the project directory if it already exists"),
+        )
+        .arg(
+            Arg::new("merge")
+                .long("merge")
+                .action(ArgAction::SetTrue)
+                .help("Adds the template to an existing project directory, asking before overwriting files"),
+        )
+        .arg(
+            Arg::new("keep-on-failure")
+                .long("keep-on-failure")
+                .action(ArgAction::SetTrue)
+                .help("Keeps the partially generated project when setup fails, for debugging"),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .help("Prints every step of the setup without writing anything or running commands"),
+        )
+        .arg(
+            Arg::new("no-hooks")
+                .long("no-hooks")
+                .action(ArgAction::SetTrue)
+                .help("Skips the template's and your own hooks, for templates you don't trust"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .value_name("FORMAT")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Sets the output format; json prints one event per line for tools that wrap genesis, and implies --yes"),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .conflicts_with("verbose")
+                .help("Prints nothing but errors"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .global(true)
+                .action(ArgAction::Count)
+                .help("Prints what each step does; repeat (-vv) to also list every file and check"),
+        )
+        .arg(
+            Arg::new("log-file")
+                .long("log-file")
+                .value_name("PATH")
+                .global(true)
+                .help("Appends a detailed log of the run to a file"),
+        )
+        .arg(
+            Arg::new("no-color")
+                .long("no-color")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Disables colored output (also set by the NO_COLOR environment variable)"),
+        )
         .get_matches();
 
-    if let Some(_) = matches.subcommand_matches("update") {
+    if matches.get_flag("no-color") || output::no_color_env() {
+        output::disable_colors();
+    }
+    logging::init(
+        Verbosity::from_flags(matches.get_flag("quiet"), matches.get_count("verbose")),
+        matches
+            .get_one::<String>("log-file")
+            .map(|path| PathBuf::from(shellexpand::tilde(path).into_owned()))
+            .as_deref(),
+    )?;
+
+    output::set_format(
+        match matches.get_one::<String>("output").unwrap().as_str() {
+            "json" => OutputFormat::Json,
+            _ => OutputFormat::Text,
+        },
+    );
+    print_banner();
+
+    if matches.subcommand_matches("update").is_some() {
         return update_genesis();
     }
 
-    let language = match matches.get_one::<String>("language").map(|s| s.as_str()) {
-        Some(lang) => lang.to_string(),
-        None => {
-            let spinner = ProgressBar::new_spinner();
-            spinner.set_style(spinner_style.clone());
-            spinner.set_message("Preparing language options...");
-            spinner.enable_steady_tick(100);
-            thread::sleep(Duration::from_secs(1));
-            spinner.finish_and_clear();
-
-            prompt_step(&term, "Choose your project language:", || {
-                let options = &["Go", "Rust", "React"];
-                let selection = Select::with_theme(&ColorfulTheme::default())
+    if matches.subcommand_matches("doctor").is_some() {
+        return run_doctor();
+    }
+
+    if let Some(cache_matches) = matches.subcommand_matches("cache") {
+        return match cache_matches.subcommand_name() {
+            Some("list") => list_cache(),
+            Some("update") => update_cache(&Registry::load()?),
+            Some("clean") => clean_cache(),
+            _ => unreachable!(),
+        };
+    }
+
+    // Prompts are only shown when a user can answer them; otherwise every
+    // value has to come from a flag or fall back to its default.
+    let interactive = !matches.get_flag("yes") && !output::is_json() && io::stdin().is_terminal();
+
+    let vars = matches
+        .get_many::<String>("var")
+        .unwrap_or_default()
+        .map(|var| parse_var(var))
+        .collect::<Result<HashMap<_, _>, _>>()?;
+
+    let existing = if matches.get_flag("force") {
+        ExistingDestination::Overwrite
+    } else if matches.get_flag("merge") {
+        ExistingDestination::Merge
+    } else {
+        ExistingDestination::Abort
+    };
+    let dry_run = matches.get_flag("dry-run");
+
+    let registry = Registry::load()?;
+
+    let mut template = match (
+        matches.get_one::<String>("template"),
+        matches.get_one::<String>("language"),
+    ) {
+        (Some(source), _) => {
+            let mut template = Template::from_source(source);
+            template.hooks = registry.hooks().clone();
+            template
+        }
+        (None, Some(query)) => registry.find(query).cloned().ok_or_else(|| {
+            GenesisError::Validation(format!("Unknown language or template: {}", query))
+        })?,
+        (None, None) => {
+            require_interactive(interactive, "language")?;
+            let templates = registry.templates();
+            let selection = prompt_step(&term, "Choose your project language:", || {
+                let options: Vec<&str> = templates.iter().map(|t| t.title()).collect();
+                Ok(Select::with_theme(&ColorfulTheme::default())
                     .with_prompt("Select your preferred language")
-                    .items(&options[..])
+                    .items(&options)
                     .default(0)
-                    .interact_on(&term)?;
-                Ok(options[selection].to_lowercase())
-            })?
+                    .interact_on(&term)?)
+            })?;
+            templates[selection].clone()
         }
     };
+    if let Some(git_ref) = matches.get_one::<String>("ref") {
+        template.git_ref = Some(git_ref.clone());
+    }
+    if matches.get_flag("full-history") {
+        template.shallow = false;
+    }
+    let language = template.language.clone();
+    let template_name = template.name.clone();
 
-    let project_name = prompt_step(&term, "Enter your project name:", || {
-        Ok(Input::<String>::new()
-            .with_prompt("Project name")
-            .interact_text()?)
-    })?;
-
-    let path = prompt_step(&term, "Enter the project path:", || {
-        let input: String = Input::new()
-            .with_prompt("Project path (press Enter for default)")
-            .with_initial_text(&default_path)
-            .allow_empty(true)
-            .interact_text()?;
-
-        if input.is_empty() {
-            Ok(PathBuf::from(default_path))
-        } else {
-            Ok(PathBuf::from(shellexpand::tilde(&input).into_owned()))
+    let project_name = match matches.get_one::<String>("name") {
+        Some(name) => {
+            validate_project_name(name, &language).map_err(GenesisError::Validation)?;
+            name.clone()
+        }
+        None => {
+            require_interactive(interactive, "name")?;
+            prompt_step(&term, "Enter your project name:", || {
+                Ok(Input::<String>::new()
+                    .with_prompt("Project name")
+                    .validate_with(|name: &String| validate_project_name(name, &language))
+                    .interact_text()?)
+            })?
         }
-    })?;
+    };
+
+    let path = match matches.get_one::<String>("path") {
+        Some(path) => expand_path(path)?,
+        None if !interactive => PathBuf::from(&default_path),
+        None => prompt_step(&term, "Enter the project path:", || {
+            let input: String = Input::new()
+                .with_prompt("Project path (press Enter for default)")
+                .with_initial_text(&default_path)
+                .allow_empty(true)
+                .interact_text()?;
+
+            if input.is_empty() {
+                Ok(PathBuf::from(default_path))
+            } else {
+                expand_path(&input)
+            }
+        })?,
+    };
+
+    let mut generator = Generator::new(template)
+        .name(&project_name)
+        .destination(&path)
+        .vars(vars)
+        .interactive(interactive)
+        .dry_run(dry_run)
+        .keep_on_failure(matches.get_flag("keep-on-failure"))
+        .existing(existing)
+        .keep_history(matches.get_flag("keep-history"))
+        .hooks(!matches.get_flag("no-hooks"));
+    if let Some(module) = matches.get_one::<String>("module") {
+        generator = generator.module(module);
+    }
+    if let Some(database) = matches.get_one::<String>("database") {
+        generator = generator.database(database);
+    }
+    if let Some(rust_template) = matches.get_one::<String>("rust-template") {
+        generator = generator.rust_template(rust_template);
+    }
+    if matches.get_flag("initial-commit") {
+        generator = generator.initial_commit(
+            matches.get_one::<String>("commit-message").unwrap(),
+            matches.get_one::<String>("commit-author").cloned(),
+        );
+    }
+    if output::is_json() {
+        generator = generator.on_event(output::emit_event);
+    }
+    generator.check()?;
 
-    println!(
+    say!(
         "\n{}",
         "Project Configuration Summary:".bright_cyan().bold()
     );
-    println!("  {} {}", "Language:".bright_yellow(), language);
-    println!("  {} {}", "Project Name:".bright_yellow(), project_name);
-    println!("  {} {}", "Path:".bright_yellow(), path.display());
-
-    let confirm = Select::with_theme(&ColorfulTheme::default())
-        .with_prompt("Do you want to proceed with this configuration?")
-        .items(&["Yes, let's go!", "No, I want to start over"])
-        .default(0)
-        .interact_on(&term)?;
-
-    if confirm == 1 {
-        println!("{}", "Starting over...".bright_yellow());
-        return Ok(());
+    say!("  {} {}", "Language:".bright_yellow(), language);
+    if !generator.template().is_builtin() {
+        say!(
+            "  {} {}",
+            "Template:".bright_yellow(),
+            generator.template().title()
+        );
     }
+    say!("  {} {}", "Project Name:".bright_yellow(), project_name);
+    say!("  {} {}", "Path:".bright_yellow(), path.display());
 
-    let progress_bar = ProgressBar::new(100);
-    progress_bar.set_style(progress_style);
+    if interactive {
+        let confirm = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Do you want to proceed with this configuration?")
+            .items(&["Yes, let's go!", "No, I want to start over"])
+            .default(0)
+            .interact_on(&term)?;
 
-    match language.as_str() {
-        "go" => {
-            let module_name = prompt_step(&term, "Enter your Go module name:", || {
-                Ok(Input::<String>::new()
-                    .with_prompt("Go module name (e.g., github.com/username/project)")
-                    .interact_text()?)
-            })?;
-            let database = prompt_database_selection(&term)?;
-
-            for i in 0..=100 {
-                progress_bar.set_position(i);
-                progress_bar.set_message(format!("Setting up Go project: {}%", i));
-                thread::sleep(Duration::from_millis(50));
-            }
-            progress_bar.finish_with_message("Go project setup complete!");
-
-            setup_go_project(
-                path.to_str().unwrap(),
-                &project_name,
-                &module_name,
-                &database,
-            )?
+        if confirm == 1 {
+            say!("{}", "Starting over...".bright_yellow());
+            return Ok(());
         }
-        "rust" => {
-            for i in 0..=100 {
-                progress_bar.set_position(i);
-                progress_bar.set_message(format!("Setting up Rust project: {}%", i));
-                thread::sleep(Duration::from_millis(50));
-            }
-            progress_bar.finish_with_message("Rust project setup complete!");
+    }
 
-            setup_rust_project(path.to_str().unwrap(), &project_name)?
-        }
-        "react" => {
-            for i in 0..=100 {
-                progress_bar.set_position(i);
-                progress_bar.set_message(format!("Setting up React project: {}%", i));
-                thread::sleep(Duration::from_millis(50));
-            }
-            progress_bar.finish_with_message("React project setup complete!");
+    let project_path = generator.generate()?;
+    if dry_run {
+        say!(
+            "\n{}",
+            "Dry run complete, nothing was written."
+                .bright_green()
+                .bold()
+        );
+    }
 
-            setup_react_ts_vite_project(path.to_str().unwrap(), &project_name)?
-        }
-        _ => println!(
-            "{}",
-            style(format!("Unsupported language: {}", language)).red()
-        ),
+    output::emit(
+        "summary",
+        json!({
+            "success": true,
+            "project_name": project_name,
+            "project_path": project_path,
+            "template": template_name,
+            "language": language,
+            "dry_run": dry_run,
+        }),
+    );
+    if dry_run {
+        return Ok(());
     }
 
-    println!(
+    say!(
         "\n{}",
         "Project setup completed successfully!"
             .bright_green()
             .bold()
     );
-    println!(
+    say!(
         "{}",
         format!(
             "Your new {} project '{}' is ready at {}",
@@ -196,7 +481,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         )
         .bright_cyan()
     );
-    println!("\n{}", "Happy coding! 🚀".bright_yellow().bold());
+    say!("\n{}", "Happy coding! 🚀".bright_yellow().bold());
 
     Ok(())
 }
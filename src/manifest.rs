@@ -0,0 +1,599 @@
+use dialoguer::{theme::ColorfulTheme, Input, Select};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Component, Path},
+};
+
+use crate::error::GenesisError;
+use crate::event::{Event, RewriteAction};
+use crate::file::replace_in_files;
+use crate::hooks::Hooks;
+use crate::render::{is_truthy, render_project};
+use crate::setup::SetupOptions;
+use crate::utils::print_dry_run;
+
+pub const MANIFEST_FILE: &str = "genesis.toml";
+
+/// Describes how a cloned template turns into a project. Template authors
+/// commit it as `genesis.toml` at the root of the starter repository.
+///
+/// A template with a manifest also has its file contents and names rendered
+/// with the resolved variables (see `render::render`).
+#[derive(Debug, Default, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub variables: Vec<Variable>,
+    #[serde(default)]
+    pub replace: Vec<Replacement>,
+    /// Commands run after the template has been rewritten. When present they
+    /// take the place of the registry's post-setup steps.
+    #[serde(default)]
+    pub commands: Vec<String>,
+    /// Paths that only ship when the named variable is truthy, e.g.
+    /// `postgresql = ["internal/infrastructure/postgres"]`. A path listed
+    /// under several options is kept if any of them is selected.
+    #[serde(default)]
+    pub paths: BTreeMap<String, Vec<String>>,
+    /// Shell commands run at stages of the setup, run before the user's
+    /// hooks for the same stage.
+    #[serde(default)]
+    pub hooks: Hooks,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Variable {
+    pub name: String,
+    #[serde(default)]
+    pub prompt: Option<String>,
+    #[serde(default)]
+    pub default: Option<String>,
+    /// Restricts the value to one of these. Each choice is also set as a
+    /// variable of its own, `"true"` when chosen, for use in `{{#if}}`.
+    #[serde(default)]
+    pub choices: Vec<String>,
+}
+
+/// Replaces every occurrence of `placeholder` with the value of `variable`.
+#[derive(Debug, Deserialize)]
+pub struct Replacement {
+    pub placeholder: String,
+    pub variable: String,
+    /// Paths relative to the project root; every file when empty.
+    #[serde(default)]
+    pub files: Vec<String>,
+}
+
+impl Manifest {
+    /// Reads the manifest from a cloned template, if it has one.
+    pub fn load(project_path: &Path) -> Result<Option<Self>, GenesisError> {
+        let path = project_path.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&path)?;
+        let manifest: Manifest = toml_edit::de::from_str(&content).map_err(|e| {
+            GenesisError::Template(format!(
+                "Invalid template manifest {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        if manifest.replace.iter().any(|r| r.placeholder.is_empty()) {
+            return Err(GenesisError::Template(format!(
+                "Invalid template manifest {}: a replacement's placeholder is empty",
+                path.display()
+            )));
+        }
+        Ok(Some(manifest))
+    }
+
+    /// Collects a value for every declared variable, taking values passed
+    /// with `--var` first, then prompting, then falling back to the default.
+    /// `project_name` and, when git knows it, `author` are always available.
+    pub fn resolve_variables(
+        &self,
+        project_name: &str,
+        provided: &HashMap<String, String>,
+        interactive: bool,
+    ) -> Result<HashMap<String, String>, GenesisError> {
+        let mut values = provided.clone();
+        values
+            .entry("project_name".to_string())
+            .or_insert_with(|| project_name.to_string());
+        if let Some(author) = git_author() {
+            values.entry("author".to_string()).or_insert(author);
+        }
+
+        for variable in &self.variables {
+            if let Some(value) = values.get(&variable.name) {
+                if !variable.choices.is_empty() && !variable.choices.contains(value) {
+                    return Err(GenesisError::Validation(format!(
+                        "Invalid value '{}' for {}, expected one of: {}",
+                        value,
+                        variable.name,
+                        variable.choices.join(", ")
+                    )));
+                }
+                continue;
+            }
+
+            let value = if interactive && !variable.choices.is_empty() {
+                let default = variable
+                    .default
+                    .as_ref()
+                    .and_then(|d| variable.choices.iter().position(|c| c == d))
+                    .unwrap_or(0);
+                let selection = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt(variable.prompt.as_deref().unwrap_or(&variable.name))
+                    .items(&variable.choices)
+                    .default(default)
+                    .interact()?;
+                variable.choices[selection].clone()
+            } else if interactive {
+                let mut input = Input::<String>::new();
+                input.with_prompt(variable.prompt.as_deref().unwrap_or(&variable.name));
+                if let Some(default) = &variable.default {
+                    input.default(default.clone());
+                }
+                input.interact_text()?
+            } else {
+                variable.default.clone().ok_or_else(|| {
+                    GenesisError::Validation(format!(
+                        "missing required value: pass `--var {}=<value>` when running non-interactively",
+                        variable.name
+                    ))
+                })?
+            };
+            values.insert(variable.name.clone(), value);
+        }
+
+        for variable in self.variables.iter().filter(|v| !v.choices.is_empty()) {
+            let chosen = values[&variable.name].clone();
+            for choice in &variable.choices {
+                values
+                    .entry(choice.clone())
+                    .or_insert_with(|| (*choice == chosen).to_string());
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Prunes the paths of unselected options, removes the manifest, which
+    /// is template metadata rather than part of the generated project, then
+    /// applies the declared replacements and renders the project. Returns
+    /// the pruned paths.
+    ///
+    /// For a dry run nothing is changed; each change is reported instead.
+    pub fn apply(
+        &self,
+        project_path: &Path,
+        values: &HashMap<String, String>,
+        options: &SetupOptions,
+    ) -> Result<Vec<String>, GenesisError> {
+        let dry_run = options.dry_run;
+        let pruned = self.prune_paths(project_path, values, dry_run)?;
+        let relative = |path: &Path| {
+            path.strip_prefix(project_path)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        };
+        // A dry run leaves the manifest in place, so it's left out by hand.
+        let manifest_path = project_path.join(MANIFEST_FILE);
+        let is_manifest = |path: &Path| path == manifest_path;
+
+        if dry_run {
+            print_dry_run(format!("Would remove {}", MANIFEST_FILE));
+        } else {
+            fs::remove_file(&manifest_path)?;
+        }
+
+        for replacement in &self.replace {
+            let value = values.get(&replacement.variable).ok_or_else(|| {
+                GenesisError::Template(format!(
+                    "Template manifest replaces '{}' with unknown variable '{}'",
+                    replacement.placeholder, replacement.variable
+                ))
+            })?;
+            let updated = replace_in_files(
+                project_path,
+                &replacement.files,
+                &replacement.placeholder,
+                value,
+                dry_run,
+            )?;
+            if dry_run {
+                for path in updated.iter().filter(|path| !is_manifest(path)) {
+                    print_dry_run(format!(
+                        "Would replace '{}' with '{}' in {}",
+                        replacement.placeholder,
+                        value,
+                        relative(path)
+                    ));
+                }
+            } else {
+                options.events.emit(Event::FilesRewritten {
+                    action: RewriteAction::Replace,
+                    placeholder: Some(replacement.placeholder.clone()),
+                    files: updated.iter().map(|p| relative(p)).collect(),
+                });
+            }
+        }
+
+        let rendered = render_project(project_path, values, dry_run)?;
+        if dry_run {
+            for (path, target) in rendered.iter().filter(|(path, _)| !is_manifest(path)) {
+                if path == target {
+                    print_dry_run(format!("Would render {}", relative(path)));
+                } else {
+                    print_dry_run(format!(
+                        "Would render {} as {}",
+                        relative(path),
+                        relative(target)
+                    ));
+                }
+            }
+        } else {
+            options.events.emit(Event::FilesRewritten {
+                action: RewriteAction::Render,
+                placeholder: None,
+                files: rendered
+                    .iter()
+                    .map(|(_, target)| relative(target))
+                    .collect(),
+            });
+        }
+        Ok(pruned)
+    }
+
+    fn prune_paths(
+        &self,
+        project_path: &Path,
+        values: &HashMap<String, String>,
+        dry_run: bool,
+    ) -> Result<Vec<String>, GenesisError> {
+        let mut keep = Vec::new();
+        let mut remove = Vec::new();
+        for (option, paths) in &self.paths {
+            for path in paths {
+                if !Path::new(path)
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_)))
+                {
+                    return Err(GenesisError::Template(format!(
+                        "Template manifest path '{}' must be relative to the project root",
+                        path
+                    )));
+                }
+
+                if is_truthy(values.get(option)) {
+                    keep.push(path);
+                } else {
+                    remove.push(path);
+                }
+            }
+        }
+
+        let mut pruned = Vec::new();
+        for path in remove {
+            if keep.contains(&path) || pruned.contains(path) {
+                continue;
+            }
+
+            let full_path = project_path.join(path);
+            if !full_path.exists() {
+                continue;
+            } else if dry_run {
+                print_dry_run(format!("Would remove unused path: {}", path));
+            } else if full_path.is_dir() {
+                fs::remove_dir_all(&full_path)?;
+            } else {
+                fs::remove_file(&full_path)?;
+            }
+            pruned.push(path.clone());
+        }
+
+        Ok(pruned)
+    }
+}
+
+fn git_author() -> Option<String> {
+    git2::Config::open_default()
+        .and_then(|config| config.get_string("user.name"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Events;
+    use std::sync::{Arc, Mutex};
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn manifest(toml: &str) -> Manifest {
+        toml_edit::de::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn loads_manifests() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Manifest::load(dir.path()).unwrap().is_none());
+
+        let cases = [
+            ("commands = [\"go mod tidy\"]", None),
+            ("unknown = 1\n[[variables]]\nname = \"x\"", None),
+            (
+                "[[variables]]\nprompt = \"no name\"",
+                Some("Invalid template manifest"),
+            ),
+            (
+                "commands = \"go mod tidy\"",
+                Some("Invalid template manifest"),
+            ),
+            (
+                "[[replace]]\nplaceholder = \"\"\nvariable = \"project_name\"",
+                Some("placeholder is empty"),
+            ),
+        ];
+        for (toml, error) in cases {
+            fs::write(dir.path().join(MANIFEST_FILE), toml).unwrap();
+            match (Manifest::load(dir.path()), error) {
+                (Ok(manifest), None) => assert!(manifest.is_some(), "{}", toml),
+                (Err(e), Some(error)) => assert!(e.to_string().contains(error), "{}: {}", toml, e),
+                (result, _) => panic!("{}: {:?}", toml, result.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn resolves_variables() {
+        let manifest = manifest(
+            r#"
+            [[variables]]
+            name = "module"
+            default = "github.com/acme/service"
+
+            [[variables]]
+            name = "database"
+            choices = ["mongodb", "postgresql"]
+            default = "mongodb"
+            "#,
+        );
+        let cases = [
+            (
+                values(&[]),
+                values(&[
+                    ("project_name", "demo"),
+                    ("module", "github.com/acme/service"),
+                    ("database", "mongodb"),
+                    ("mongodb", "true"),
+                    ("postgresql", "false"),
+                ]),
+            ),
+            (
+                values(&[
+                    ("module", "example.com/me/demo"),
+                    ("database", "postgresql"),
+                    ("project_name", "other"),
+                    ("extra", "kept"),
+                ]),
+                values(&[
+                    ("project_name", "other"),
+                    ("module", "example.com/me/demo"),
+                    ("database", "postgresql"),
+                    ("mongodb", "false"),
+                    ("postgresql", "true"),
+                    ("extra", "kept"),
+                ]),
+            ),
+        ];
+        for (provided, expected) in cases {
+            let mut resolved = manifest
+                .resolve_variables("demo", &provided, false)
+                .unwrap();
+            resolved.remove("author");
+            assert_eq!(resolved, expected);
+        }
+    }
+
+    #[test]
+    fn rejects_unresolved_variables() {
+        let manifest = manifest(
+            r#"
+            [[variables]]
+            name = "module"
+
+            [[variables]]
+            name = "database"
+            choices = ["mongodb", "postgresql"]
+            default = "mongodb"
+            "#,
+        );
+        let cases = [
+            (values(&[]), "--var module=<value>"),
+            (
+                values(&[("module", "m"), ("database", "sqlite")]),
+                "expected one of: mongodb, postgresql",
+            ),
+        ];
+        for (provided, error) in cases {
+            let e = manifest
+                .resolve_variables("demo", &provided, false)
+                .unwrap_err();
+            assert!(e.to_string().contains(error), "{}", e);
+        }
+    }
+
+    #[test]
+    fn applies_replacements_and_renders() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("go.mod"), "module github.com/acme/starter").unwrap();
+        fs::write(root.join("src/main.go"), "// {{project_name}} by ACME").unwrap();
+        let toml = r#"
+            [[replace]]
+            placeholder = "github.com/acme/starter"
+            variable = "module"
+            files = ["go.mod"]
+
+            [[replace]]
+            placeholder = "ACME"
+            variable = "author"
+        "#;
+        fs::write(root.join(MANIFEST_FILE), toml).unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let received = events.clone();
+        let options = SetupOptions {
+            events: Events::new(Arc::new(move |event: &Event| {
+                received.lock().unwrap().push(event.clone())
+            })),
+            ..SetupOptions::default()
+        };
+        let values = values(&[
+            ("project_name", "demo"),
+            ("module", "example.com/me/demo"),
+            ("author", "Me"),
+        ]);
+        let pruned = manifest(toml).apply(root, &values, &options).unwrap();
+
+        assert!(pruned.is_empty());
+        assert!(!root.join(MANIFEST_FILE).exists());
+        assert_eq!(
+            fs::read_to_string(root.join("go.mod")).unwrap(),
+            "module example.com/me/demo"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("src/main.go")).unwrap(),
+            "// demo by Me"
+        );
+        let files: Vec<_> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|event| match event {
+                Event::FilesRewritten { action, files, .. } => (*action, files.clone()),
+                event => panic!("unexpected {:?}", event),
+            })
+            .collect();
+        assert_eq!(
+            files,
+            [
+                (RewriteAction::Replace, vec!["go.mod".to_string()]),
+                (
+                    RewriteAction::Replace,
+                    vec![Path::new("src").join("main.go").display().to_string()]
+                ),
+                (
+                    RewriteAction::Render,
+                    vec![Path::new("src").join("main.go").display().to_string()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn prunes_paths_of_unselected_options() {
+        let manifest = manifest(
+            r#"
+            [paths]
+            postgresql = ["internal/postgres", "internal/shared.go", "docs/pg.md"]
+            mongodb = ["internal/mongo", "internal/shared.go"]
+            redis = ["internal/cache"]
+            "#,
+        );
+        let cases = [
+            (
+                values(&[("postgresql", "true"), ("mongodb", "false")]),
+                vec!["internal/mongo", "internal/cache"],
+            ),
+            (
+                values(&[("mongodb", "yes")]),
+                vec!["internal/postgres", "docs/pg.md", "internal/cache"],
+            ),
+            (
+                values(&[]),
+                vec![
+                    "internal/postgres",
+                    "internal/shared.go",
+                    "docs/pg.md",
+                    "internal/mongo",
+                    "internal/cache",
+                ],
+            ),
+        ];
+        for (values, expected) in cases {
+            let dir = tempfile::tempdir().unwrap();
+            let root = dir.path();
+            for dir in [
+                "internal/postgres",
+                "internal/mongo",
+                "internal/cache",
+                "docs",
+            ] {
+                fs::create_dir_all(root.join(dir)).unwrap();
+            }
+            fs::write(root.join("internal/shared.go"), "").unwrap();
+            fs::write(root.join("docs/pg.md"), "").unwrap();
+
+            let mut pruned = manifest.prune_paths(root, &values, false).unwrap();
+            let mut expected: Vec<String> = expected.iter().map(|p| p.to_string()).collect();
+            pruned.sort();
+            expected.sort();
+            assert_eq!(pruned, expected, "{:?}", values);
+            for path in &pruned {
+                assert!(!root.join(path).exists(), "{}", path);
+            }
+            assert!(root.join("docs").is_dir());
+        }
+    }
+
+    #[test]
+    fn rejects_paths_outside_the_project() {
+        for path in ["../outside", "/etc", "internal/../../outside"] {
+            let manifest = manifest(&format!(
+                "[paths]
+x = [{:?}]",
+                path
+            ));
+            let dir = tempfile::tempdir().unwrap();
+            let error = manifest
+                .prune_paths(dir.path(), &HashMap::new(), false)
+                .unwrap_err();
+            assert!(error.to_string().contains("relative"), "{}", path);
+        }
+    }
+
+    #[test]
+    fn dry_run_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("README.md"), "# {{project_name}} ACME").unwrap();
+        let toml = "[[replace]]\nplaceholder = \"ACME\"\nvariable = \"project_name\"";
+        fs::write(root.join(MANIFEST_FILE), toml).unwrap();
+
+        let options = SetupOptions {
+            dry_run: true,
+            ..SetupOptions::default()
+        };
+        manifest(toml)
+            .apply(root, &values(&[("project_name", "demo")]), &options)
+            .unwrap();
+        assert!(root.join(MANIFEST_FILE).exists());
+        assert_eq!(
+            fs::read_to_string(root.join("README.md")).unwrap(),
+            "# {{project_name}} ACME"
+        );
+    }
+}
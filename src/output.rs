@@ -0,0 +1,95 @@
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+
+use crate::event::Event;
+use crate::logging::{self, Verbosity};
+
+/// How genesis reports what it does: text for people, or one JSON object
+/// per line for tools that wrap it. Until a format is set, as when genesis
+/// is used as a library, nothing is printed at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// Picks the output format for the rest of the run. JSON output turns off
+/// colors, so nothing but JSON ends up on stdout.
+pub fn set_format(format: OutputFormat) {
+    let _ = FORMAT.set(format);
+    if format == OutputFormat::Json {
+        disable_colors();
+    }
+}
+
+/// Turns off colors and text styles everywhere, for `--no-color` and
+/// `NO_COLOR`.
+pub fn disable_colors() {
+    console::set_colors_enabled(false);
+    console::set_colors_enabled_stderr(false);
+    colored::control::set_override(false);
+}
+
+/// Whether `NO_COLOR` asks for plain output (see https://no-color.org).
+pub fn no_color_env() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+pub fn is_json() -> bool {
+    FORMAT.get() == Some(&OutputFormat::Json)
+}
+
+pub fn is_text() -> bool {
+    FORMAT.get() == Some(&OutputFormat::Text)
+}
+
+/// Whether messages for people are printed: only with text output, and not
+/// with `-q`.
+pub fn shows_messages() -> bool {
+    is_text() && logging::verbosity() > Verbosity::Quiet
+}
+
+/// Writes an event as a line of JSON on stdout, e.g.
+/// `{"event":"command","command":"go mod tidy","exit_code":0}`. Does
+/// nothing with text output.
+pub fn emit(event: &str, fields: Value) {
+    if !is_json() {
+        return;
+    }
+    let mut object = json!({ "event": event });
+    if let (Some(object), Value::Object(fields)) = (object.as_object_mut(), fields) {
+        object.extend(fields);
+    }
+    println!("{}", object);
+}
+
+/// Writes an event from the generator as a line of JSON on stdout. Does
+/// nothing with text output.
+pub fn emit_event(event: &Event) {
+    if let Ok(Value::Object(mut fields)) = serde_json::to_value(event) {
+        if let Some(Value::String(name)) = fields.remove("event") {
+            emit(&name, Value::Object(fields));
+        }
+    }
+}
+
+/// `println!` for messages meant for people, which are left out of JSON
+/// output and `-q`, and copied to the log file.
+#[macro_export]
+macro_rules! say {
+    () => {
+        if $crate::output::shows_messages() {
+            println!();
+        }
+    };
+    ($($arg:tt)*) => {{
+        let message = format!($($arg)*);
+        if $crate::output::shows_messages() {
+            println!("{}", message);
+        }
+        $crate::logging::to_file(&message);
+    }};
+}
+pub use crate::say;
@@ -0,0 +1,58 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::{sync::OnceLock, time::Duration};
+
+use crate::output;
+
+/// The view every progress bar and spinner is drawn in, so clones, file
+/// rewrites and commands stack up in one place and lines printed through
+/// their bars appear above them instead of breaking them up. Nothing is
+/// drawn unless messages are shown, so not with JSON output or `-q`.
+fn multi() -> &'static MultiProgress {
+    static MULTI: OnceLock<MultiProgress> = OnceLock::new();
+    MULTI.get_or_init(|| {
+        if output::shows_messages() {
+            MultiProgress::new()
+        } else {
+            MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+        }
+    })
+}
+
+/// Runs `f` with every progress bar hidden, for printing around them.
+pub fn suspend<R>(f: impl FnOnce() -> R) -> R {
+    multi().suspend(f)
+}
+
+/// A bar counting `len` items, e.g. the objects of a clone or the files
+/// being rewritten. A `len` of 0 means it isn't known yet.
+pub fn bar(len: u64, message: impl Into<String>) -> ProgressBar {
+    let pb = match len {
+        0 => ProgressBar::no_length(),
+        len => ProgressBar::new(len),
+    };
+    let pb = multi().add(pb);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos:>7}/{len:7} {msg}",
+            )
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+    pb.set_message(message.into());
+    pb
+}
+
+/// A spinner for work whose length isn't known, such as a running command.
+pub fn spinner(message: impl Into<String>) -> ProgressBar {
+    let pb = multi().add(ProgressBar::new_spinner());
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ")
+            .template("{spinner:.green} {msg}")
+            .unwrap(),
+    );
+    pb.set_message(message.into());
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb
+}
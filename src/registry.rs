@@ -0,0 +1,310 @@
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use crate::constants::{GO_URL, REACT_VITE_TYPESCRIPT_URL, RUST_URL};
+use crate::error::GenesisError;
+use crate::hooks::Hooks;
+
+/// A starter project genesis knows how to generate.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Template {
+    pub name: String,
+    /// Label shown in the template picker, defaults to `name`.
+    #[serde(default)]
+    pub title: Option<String>,
+    pub language: String,
+    pub url: String,
+    #[serde(default, rename = "ref")]
+    pub git_ref: Option<String>,
+    /// Sub-directory of the repository that holds the template, for
+    /// repositories with several starters.
+    #[serde(default, rename = "path")]
+    pub subdir: Option<String>,
+    /// Commands run inside the new project once it has been cloned.
+    #[serde(default)]
+    pub steps: Vec<String>,
+    /// Programs the template needs, mapped to their minimum version, e.g.
+    /// `{ node = "18" }`. Use `"*"` for any version. The programs the steps
+    /// run are always required.
+    #[serde(default)]
+    pub requires: BTreeMap<String, String>,
+    /// Access token for a private template served over HTTPS. Defaults to
+    /// the registry's `[auth]` token, then to `GENESIS_GIT_TOKEN`.
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Fetch only the latest commit instead of the full history.
+    #[serde(default = "default_shallow")]
+    pub shallow: bool,
+    /// The user's hooks for this template, run after the registry's
+    /// `[hooks]` and the template's own.
+    #[serde(default)]
+    pub hooks: Hooks,
+}
+
+fn default_shallow() -> bool {
+    true
+}
+
+impl Template {
+    fn builtin(
+        name: &str,
+        title: &str,
+        url: &str,
+        steps: &[&str],
+        requires: &[(&str, &str)],
+    ) -> Self {
+        Template {
+            name: name.to_string(),
+            title: Some(title.to_string()),
+            language: name.to_string(),
+            url: url.to_string(),
+            git_ref: None,
+            subdir: None,
+            token: None,
+            steps: steps.iter().map(|s| s.to_string()).collect(),
+            requires: requires
+                .iter()
+                .map(|(tool, version)| (tool.to_string(), version.to_string()))
+                .collect(),
+            shallow: true,
+            hooks: Hooks::default(),
+        }
+    }
+
+    /// A template given directly as a source on the command line rather
+    /// than picked from the registry. The source has the form
+    /// `url[#ref][:path/to/template]`, e.g.
+    /// `https://github.com/acme/starters.git#v2:go/service`.
+    pub fn from_source(source: &str) -> Self {
+        let (url, git_ref, subdir) = match source.rsplit_once('#') {
+            Some((url, spec)) => match spec.split_once(':') {
+                Some((git_ref, subdir)) => (url, git_ref, subdir),
+                None => (url, spec, ""),
+            },
+            None => (source, "", ""),
+        };
+        let subdir = subdir.trim_matches('/');
+
+        let title = if subdir.is_empty() { url } else { subdir };
+        let title = title
+            .trim_end_matches('/')
+            .rsplit(['/', ':'])
+            .next()
+            .unwrap_or(title)
+            .trim_end_matches(".git");
+
+        Template {
+            name: source.to_string(),
+            title: Some(title.to_string()),
+            language: "custom".to_string(),
+            url: url.to_string(),
+            git_ref: (!git_ref.is_empty()).then(|| git_ref.to_string()),
+            subdir: (!subdir.is_empty()).then(|| subdir.to_string()),
+            token: None,
+            steps: Vec::new(),
+            requires: BTreeMap::new(),
+            shallow: true,
+            hooks: Hooks::default(),
+        }
+    }
+
+    /// The directory of a template whose source is a local path rather than
+    /// a URL. Its working tree is copied instead of cloned, so uncommitted
+    /// changes are picked up; use a `file://` URL to clone it instead.
+    pub fn local_dir(&self) -> Option<PathBuf> {
+        if self.url.contains("://") {
+            return None;
+        }
+        let path = PathBuf::from(shellexpand::tilde(&self.url).into_owned());
+        path.is_dir().then_some(path)
+    }
+
+    pub fn title(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Whether this template is one of the starters shipped with genesis
+    /// (possibly pointed at another URL by the registry file).
+    pub fn is_builtin(&self) -> bool {
+        matches!(self.name.as_str(), "go" | "rust" | "react")
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RegistryFile {
+    #[serde(default)]
+    auth: Auth,
+    #[serde(default)]
+    hooks: Hooks,
+    #[serde(default)]
+    templates: Vec<Template>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Auth {
+    token: Option<String>,
+}
+
+pub struct Registry {
+    templates: Vec<Template>,
+    hooks: Hooks,
+}
+
+impl Registry {
+    /// Loads the built-in templates, followed by those declared in the user's
+    /// registry file. Entries in the file replace built-ins with the same name.
+    /// The file's `[hooks]` are added to every template.
+    pub fn load() -> Result<Self, GenesisError> {
+        let mut templates = vec![
+            Template::builtin("go", "Go", GO_URL, &["go mod tidy"], &[("go", "1.21")]),
+            Template::builtin(
+                "rust",
+                "Rust",
+                RUST_URL,
+                &["cargo build"],
+                &[("cargo", "1.70")],
+            ),
+            Template::builtin(
+                "react",
+                "React",
+                REACT_VITE_TYPESCRIPT_URL,
+                &["npm install --legacy-peer-deps"],
+                &[("node", "18.0"), ("npm", "9.0")],
+            ),
+        ];
+
+        let mut hooks = Hooks::default();
+        if let Some(path) = registry_path().filter(|path| path.exists()) {
+            let content = fs::read_to_string(&path)?;
+            let file: RegistryFile = toml_edit::de::from_str(&content).map_err(|e| {
+                GenesisError::Template(format!(
+                    "Invalid template registry {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+
+            for mut template in file.templates {
+                if template.token.is_none() {
+                    template.token = file.auth.token.clone();
+                }
+                match templates.iter_mut().find(|t| t.name == template.name) {
+                    Some(existing) => *existing = template,
+                    None => templates.push(template),
+                }
+            }
+            hooks = file.hooks;
+        }
+
+        for template in &mut templates {
+            let mut template_hooks = hooks.clone();
+            template_hooks.append(&template.hooks);
+            template.hooks = template_hooks;
+        }
+        Ok(Registry { templates, hooks })
+    }
+
+    /// The hooks the registry file declares for every template, for
+    /// templates that aren't in the registry.
+    pub fn hooks(&self) -> &Hooks {
+        &self.hooks
+    }
+
+    pub fn templates(&self) -> &[Template] {
+        &self.templates
+    }
+
+    /// Looks a template up by name, falling back to the first template for
+    /// the given language.
+    pub fn find(&self, query: &str) -> Option<&Template> {
+        let query = query.to_lowercase();
+        self.templates
+            .iter()
+            .find(|t| t.name.to_lowercase() == query)
+            .or_else(|| {
+                self.templates
+                    .iter()
+                    .find(|t| t.language.to_lowercase() == query)
+            })
+    }
+}
+
+pub fn registry_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("genesis").join("templates.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_template_sources() {
+        let cases = [
+            (
+                "https://github.com/acme/starters.git",
+                "https://github.com/acme/starters.git",
+                None,
+                None,
+                "starters",
+            ),
+            (
+                "https://github.com/acme/starters.git#v2",
+                "https://github.com/acme/starters.git",
+                Some("v2"),
+                None,
+                "starters",
+            ),
+            (
+                "https://github.com/acme/starters.git#v2:go/service",
+                "https://github.com/acme/starters.git",
+                Some("v2"),
+                Some("go/service"),
+                "service",
+            ),
+            (
+                "https://github.com/acme/starters.git#:go/service/",
+                "https://github.com/acme/starters.git",
+                None,
+                Some("go/service"),
+                "service",
+            ),
+            (
+                "git@github.com:acme/starters.git",
+                "git@github.com:acme/starters.git",
+                None,
+                None,
+                "starters",
+            ),
+            (
+                "git@github.com:acme/starters.git#main",
+                "git@github.com:acme/starters.git",
+                Some("main"),
+                None,
+                "starters",
+            ),
+            (
+                "git@github.com:acme/starters.git#main:rust",
+                "git@github.com:acme/starters.git",
+                Some("main"),
+                Some("rust"),
+                "rust",
+            ),
+            (
+                "file:///srv/templates/api/",
+                "file:///srv/templates/api/",
+                None,
+                None,
+                "api",
+            ),
+            ("~/templates/api", "~/templates/api", None, None, "api"),
+        ];
+        for (source, url, git_ref, subdir, title) in cases {
+            let template = Template::from_source(source);
+            assert_eq!(template.url, url, "{}", source);
+            assert_eq!(template.git_ref.as_deref(), git_ref, "{}", source);
+            assert_eq!(template.subdir.as_deref(), subdir, "{}", source);
+            assert_eq!(template.title(), title, "{}", source);
+            assert_eq!(template.name, source);
+        }
+    }
+}
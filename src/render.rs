@@ -0,0 +1,248 @@
+use indicatif::ProgressBar;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use walkdir::{DirEntry, WalkDir};
+
+use crate::error::GenesisError;
+use crate::progress;
+
+/// Renders `{{variable}}` placeholders and `{{#if variable}} ... {{else}} ...
+/// {{/if}}` blocks. Tags that don't name a known variable, such as JSX's
+/// `style={{ ... }}` or Go templates' `{{ .Title }}`, are left untouched so
+/// templates don't have to escape them. `{{else}}` and `{{/if}}` are only
+/// control tags inside a `{{#if}}`, so Go's `{{if .User}}...{{else}}...{{end}}`
+/// is left alone too.
+pub fn render(input: &str, values: &HashMap<String, String>) -> Result<String, String> {
+    let mut output = String::with_capacity(input.len());
+    // One entry per open `{{#if}}`: whether its current branch is active.
+    let mut conditions: Vec<bool> = Vec::new();
+    let mut rest = input;
+
+    while let Some(start) = rest.find("{{") {
+        let active = conditions.iter().all(|c| *c);
+        if active {
+            output.push_str(&rest[..start]);
+        }
+
+        let Some(end) = rest[start..].find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        let raw = &rest[start..start + end + 2];
+        let tag = raw[2..raw.len() - 2].trim();
+        rest = &rest[start + end + 2..];
+
+        if let Some(name) = tag.strip_prefix("#if ") {
+            conditions.push(is_truthy(values.get(name.trim())));
+        } else if tag == "else" && !conditions.is_empty() {
+            if let Some(condition) = conditions.last_mut() {
+                *condition = !*condition;
+            }
+        } else if tag == "/if" && !conditions.is_empty() {
+            conditions.pop();
+        } else if active {
+            match values.get(tag) {
+                Some(value) => output.push_str(value),
+                None => output.push_str(raw),
+            }
+        }
+    }
+
+    if !conditions.is_empty() {
+        return Err("{{#if}} without a matching {{/if}}".to_string());
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Whether a variable enables `{{#if}}` blocks and conditional paths.
+pub fn is_truthy(value: Option<&String>) -> bool {
+    match value.map(|v| v.trim().to_lowercase()) {
+        Some(value) => !matches!(value.as_str(), "" | "false" | "0" | "no"),
+        None => false,
+    }
+}
+
+/// Renders the contents and names of every file and directory in the
+/// project, skipping `.git` and files that aren't valid UTF-8. Returns the
+/// paths whose contents or names change, each with the path it ends up at
+/// once it and its parent directories are renamed.
+pub fn render_project(
+    project_path: &Path,
+    values: &HashMap<String, String>,
+    dry_run: bool,
+) -> Result<Vec<(PathBuf, PathBuf)>, GenesisError> {
+    let entries: Vec<_> = WalkDir::new(project_path)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+        .filter_map(|e| e.ok())
+        .collect();
+
+    let pb = progress::bar(entries.len() as u64, "Rendering template...");
+    let result = render_entries(entries, values, dry_run, &pb);
+    pb.finish_and_clear();
+
+    let mut rendered = Vec::new();
+    for path in result? {
+        let relative = path.strip_prefix(project_path).unwrap_or(&path);
+        let mut target = project_path.to_path_buf();
+        for component in relative.iter() {
+            // Every name was rendered successfully already.
+            target.push(render(&component.to_string_lossy(), values).unwrap_or_default());
+        }
+        rendered.push((path, target));
+    }
+    Ok(rendered)
+}
+
+fn render_entries(
+    entries: Vec<DirEntry>,
+    values: &HashMap<String, String>,
+    dry_run: bool,
+    pb: &ProgressBar,
+) -> Result<Vec<PathBuf>, GenesisError> {
+    let mut rendered_paths = Vec::new();
+
+    // Reversed so children come before their parent directory, and renaming
+    // a directory never invalidates paths that are still to be visited.
+    for entry in entries.into_iter().rev() {
+        pb.inc(1);
+        let path = entry.path();
+
+        if entry.file_type().is_file() {
+            if let Ok(content) = fs::read_to_string(path) {
+                let rendered = render(&content, values).map_err(|e| {
+                    GenesisError::Template(format!("Failed to render {}: {}", path.display(), e))
+                })?;
+                if rendered != content {
+                    log::debug!("Rendered {}", path.display());
+                    rendered_paths.push(path.to_path_buf());
+                    if !dry_run {
+                        fs::write(path, rendered)?;
+                    }
+                }
+            }
+        }
+
+        let name = entry.file_name().to_string_lossy();
+        let rendered_name = render(&name, values).map_err(|e| {
+            GenesisError::Template(format!(
+                "Failed to render name of {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        if rendered_name != name {
+            if rendered_name.is_empty() {
+                return Err(GenesisError::Template(format!(
+                    "{} renders to an empty name",
+                    path.display()
+                )));
+            }
+            if !rendered_paths.iter().any(|p| p == path) {
+                rendered_paths.push(path.to_path_buf());
+            }
+            if !dry_run {
+                fs::rename(path, path.with_file_name(rendered_name))?;
+            }
+        }
+    }
+
+    Ok(rendered_paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn renders_variables_and_leaves_unknown_tags() {
+        let values = values(&[("name", "demo")]);
+        let cases = [
+            ("Hello {{name}}!", "Hello demo!"),
+            ("Hello {{ name }}", "Hello demo"),
+            ("{{ .Title }} by {{name}}", "{{ .Title }} by demo"),
+            (
+                "<div style={{ color: 'red' }} />",
+                "<div style={{ color: 'red' }} />",
+            ),
+            ("{{{{name}}}}", "{{{{name}}}}"),
+            ("no tags", "no tags"),
+            ("dangling {{name", "dangling {{name"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(render(input, &values).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn renders_conditions() {
+        let values = values(&[("pg", "true"), ("mongo", "false"), ("name", "demo")]);
+        let cases = [
+            ("{{#if pg}}pg{{/if}}", "pg"),
+            ("{{#if mongo}}mongo{{/if}}", ""),
+            ("{{#if missing}}x{{else}}y{{/if}}", "y"),
+            ("{{#if pg}}a{{else}}b{{/if}}", "a"),
+            (
+                "{{#if pg}}[{{#if mongo}}m{{else}}{{name}}{{/if}}]{{/if}}",
+                "[demo]",
+            ),
+            (
+                "{{#if mongo}}[{{#if pg}}p{{/if}}]{{else}}none{{/if}}",
+                "none",
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(render(input, &values).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn leaves_go_templates_alone() {
+        let input = "{{if .User}}hi{{else}}bye{{end}} {{/if}}";
+        assert_eq!(render(input, &HashMap::new()).unwrap(), input);
+    }
+
+    #[test]
+    fn rejects_unclosed_if() {
+        assert!(render("{{#if pg}}never closed", &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn renames_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("cmd/{{name}}")).unwrap();
+        fs::write(root.join("cmd/{{name}}/main.go"), "package {{name}}").unwrap();
+        fs::write(root.join("README.md"), "plain").unwrap();
+
+        let rendered = render_project(root, &values(&[("name", "demo")]), false).unwrap();
+        assert_eq!(
+            rendered,
+            [
+                (
+                    root.join("cmd/{{name}}/main.go"),
+                    root.join("cmd/demo/main.go")
+                ),
+                (root.join("cmd/{{name}}"), root.join("cmd/demo")),
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("cmd/demo/main.go")).unwrap(),
+            "package demo"
+        );
+        assert!(!root.join("cmd/{{name}}").exists());
+        assert_eq!(fs::read_to_string(root.join("README.md")).unwrap(), "plain");
+    }
+}
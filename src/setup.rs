@@ -1,47 +1,119 @@
 use console::style;
-use dialoguer::theme::ColorfulTheme;
-use dialoguer::Select;
-use std::io;
-use std::{path::Path, process::Command};
+use dialoguer::{theme::ColorfulTheme, Select};
+use git2::Repository;
+use indicatif::ProgressBar;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufRead, BufReader, Read},
+    path::{Component, Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    sync::Mutex,
+    thread,
+};
+use tempfile::TempDir;
+use walkdir::WalkDir;
 
-use crate::constants::{GO_URL, OLD_MODULE_NAME, REACT_VITE_TYPESCRIPT_URL, RUST_URL};
-use crate::file::update_module_name;
+use crate::constants::OLD_MODULE_NAME;
+use crate::error::GenesisError;
+use crate::event::{Event, Events};
+use crate::file::{copy_template_dir, update_module_name};
 use crate::git::clone_repo;
+use crate::hooks::{run_hooks, HookStage, Hooks};
+use crate::logging;
+use crate::manifest::{Manifest, MANIFEST_FILE};
+use crate::output::{self, say};
+use crate::progress;
+use crate::registry::Template;
+use crate::utils::print_dry_run;
 use crate::utils::update_cargo_toml;
 use crate::utils::update_database_config;
+use crate::utils::write_template_lock;
+
+/// How a project is set up, beyond which template and where.
+#[derive(Default)]
+pub struct SetupOptions {
+    /// Values for the template's manifest variables given with `--var`.
+    pub vars: HashMap<String, String>,
+    /// Whether missing values may be prompted for.
+    pub interactive: bool,
+    /// Print every change instead of making it.
+    pub dry_run: bool,
+    /// Keep the partial project of a failed setup.
+    pub keep_on_failure: bool,
+    /// What to do when the destination already exists.
+    pub existing: ExistingDestination,
+    /// Run the template's and the user's hooks.
+    pub run_hooks: bool,
+    /// Where the project ends up. It's generated in a staging directory
+    /// first, but hooks are told this path.
+    pub destination: PathBuf,
+    /// Receives the events of the setup.
+    pub(crate) events: Events,
+}
+
+/// How a project is written to a destination that already exists and isn't
+/// empty.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExistingDestination {
+    /// Refuse to touch it.
+    #[default]
+    Abort,
+    /// Replace it with the generated project.
+    Overwrite,
+    /// Lay the generated project over it, asking about each file that
+    /// differs.
+    Merge,
+}
+
+/// Makes sure the project can be written to `destination` before anything
+/// is generated.
+pub fn check_destination(
+    destination: &Path,
+    existing: ExistingDestination,
+) -> Result<(), GenesisError> {
+    if destination.is_file() && existing != ExistingDestination::Overwrite {
+        return Err(GenesisError::Validation(format!(
+            "Destination {} is a file; pass --force to replace it",
+            destination.display()
+        )));
+    }
+    if !destination.is_dir() || fs::read_dir(destination)?.next().is_none() {
+        return Ok(());
+    }
+
+    match existing {
+        ExistingDestination::Abort => Err(GenesisError::Validation(format!(
+            "Destination {} already exists and is not empty; pass --force to overwrite it or --merge to add the template to it",
+            destination.display()
+        ))),
+        ExistingDestination::Overwrite | ExistingDestination::Merge => Ok(()),
+    }
+}
 
 pub fn setup_react_ts_vite_project(
-    base_path: &str,
+    base_path: &Path,
     project_name: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    println!(
+    template: &Template,
+    options: &SetupOptions,
+) -> Result<(), GenesisError> {
+    say!(
         "{}",
         style("Setting up react + Vite + Typescript project...").yellow()
     );
-    println!();
-
-    let project_path = Path::new(base_path).join(project_name);
-    println!(
-        "{}",
-        style(format!(
-            "Cloning repository to {}...",
-            project_path.display()
-        ))
-        .cyan()
-    );
-    println!();
+    say!();
 
-    let _repo = clone_repo(REACT_VITE_TYPESCRIPT_URL, project_path.to_str().unwrap())?;
+    let project_path = base_path.join(project_name);
+    setup_template(
+        &project_path,
+        project_name,
+        template,
+        options,
+        options.vars.clone(),
+        |_| Ok(()),
+    )?;
 
-    Command::new("npm")
-        .arg("install")
-        .arg("--legacy-peer-deps")
-        .current_dir(&project_path)
-        .status()?;
-
-    println!();
-
-    println!(
+    say!(
         "{}",
         style("React project set up successfully!").green().bold()
     );
@@ -49,54 +121,39 @@ pub fn setup_react_ts_vite_project(
 }
 
 pub fn setup_go_project(
-    base_path: &str,
+    base_path: &Path,
     project_name: &str,
     module_name: &str,
     database: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    println!("{}", style("Setting up Go project...").yellow());
-    println!();
-
-    let project_path = Path::new(base_path).join(project_name);
-    println!(
-        "{}",
-        style(format!(
-            "Cloning repository to {}...",
-            project_path.display()
-        ))
-        .cyan()
-    );
-    println!();
-
-    let _repo = clone_repo(GO_URL, project_path.to_str().unwrap())?;
+    template: &Template,
+    options: &SetupOptions,
+) -> Result<(), GenesisError> {
+    say!("{}", style("Setting up Go project...").yellow());
+    say!();
 
-    let old_module_name = OLD_MODULE_NAME;
-    match update_module_name(&project_path, old_module_name, module_name) {
-        Ok(_) => println!("Module name updated successfully."),
-        Err(e) => println!(
-            "Error updating module name: {}. Continuing with setup...",
-            e
-        ),
-    }
-
-    // Remove unused database folder
-    // remove_unused_database_folder(&project_path, database)?;
-
-    // Update main.go with the selected database
-    // update_main_go(&project_path, database)?;
-    update_database_config(&project_path, database)?;
-    println!("{}", style("Running setup commands...").cyan());
+    let mut values = options.vars.clone();
+    values.insert("module_path".to_string(), module_name.to_string());
+    values.insert("database".to_string(), database.to_string());
+    values.insert(database.to_string(), "true".to_string());
 
-    Command::new("go")
-        .arg("mod")
-        .arg("tidy")
-        .current_dir(&project_path)
-        .status()?;
-    println!();
+    // Starters that ship a manifest use `{{module_path}}` and
+    // `{{#if postgresql}}` style placeholders instead of the hardcoded
+    // rewrites below, and list the code each database needs under `[paths]`.
+    let project_path = base_path.join(project_name);
+    setup_template(
+        &project_path,
+        project_name,
+        template,
+        options,
+        values,
+        |project_path| {
+            update_module_name(project_path, OLD_MODULE_NAME, module_name, options)?;
+            say!("Module name updated successfully.");
+            update_database_config(project_path, database, options)
+        },
+    )?;
 
-    // let _ = remove_dot_git_dir(&project_path);
-
-    println!(
+    say!(
         "{}",
         style("Go project set up successfully!").green().bold()
     );
@@ -104,132 +161,977 @@ pub fn setup_go_project(
 }
 
 pub fn setup_rust_project(
-    base_path: &str,
+    base_path: &Path,
     project_name: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    println!("{}", style("Setting up Rust project...").yellow());
-    println!();
-
-    let project_path = Path::new(base_path).join(project_name);
+    rust_template: &str,
+    template: &Template,
+    options: &SetupOptions,
+) -> Result<(), GenesisError> {
+    say!("{}", style("Setting up Rust project...").yellow());
+    say!();
 
-    let options = &["Basic Rust Project", "Full Starter template"];
-    let selection = Select::with_theme(&ColorfulTheme::default())
-        .with_prompt("Choose your Rust project type:")
-        .items(&options[..])
-        .default(0)
-        .interact()?;
+    let project_path = base_path.join(project_name);
 
-    match selection {
-        0 => setup_basic_rust_project(&project_path, project_name),
-        1 => setup_full_rust_project(&project_path, project_name),
-        _ => unreachable!(),
+    match rust_template {
+        "basic" => setup_basic_rust_project(&project_path, project_name, template, options),
+        "full" => setup_full_rust_project(&project_path, project_name, template, options),
+        _ => Err(GenesisError::Validation(format!(
+            "Unsupported Rust template: {}",
+            rust_template
+        ))),
     }
 }
 
+/// A project from `cargo new` rather than a template. Its hooks all run
+/// once it has been created, as there's nothing to render or install.
 fn setup_basic_rust_project(
     project_path: &Path,
     project_name: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    println!("{}", style("Creating a basic Rust project...").cyan());
+    template: &Template,
+    options: &SetupOptions,
+) -> Result<(), GenesisError> {
+    say!("{}", style("Creating a basic Rust project...").cyan());
 
-    Command::new("cargo")
-        .arg("new")
-        .arg(project_path)
-        .status()?;
+    if options.dry_run {
+        print_dry_run(format!("Would run: cargo new {}", project_path.display()));
+    } else {
+        let base_path = project_path.parent().unwrap_or(Path::new("."));
+        run_command("cargo", &["new", project_name], base_path, options)?;
+    }
 
-    // let _ = remove_dot_git_dir(project_path);
+    for stage in [
+        HookStage::PostClone,
+        HookStage::PostRender,
+        HookStage::PostInstall,
+    ] {
+        run_hooks(stage, &template.hooks, project_path, &options.vars, options)?;
+    }
 
-    println!(
-        "{}",
-        style(format!(
-            "Basic Rust project '{}' created successfully!",
-            project_name
-        ))
-        .green()
-        .bold()
-    );
+    if !options.dry_run {
+        say!(
+            "{}",
+            style(format!(
+                "Basic Rust project '{}' created successfully!",
+                project_name
+            ))
+            .green()
+            .bold()
+        );
+    }
     Ok(())
 }
 
 fn setup_full_rust_project(
     project_path: &Path,
     project_name: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    println!("{}", style("Setting up Rust project...").yellow());
-    println!();
+    template: &Template,
+    options: &SetupOptions,
+) -> Result<(), GenesisError> {
+    say!("{}", style("Setting up Rust project...").yellow());
+    say!();
 
-    // let project_path = Path::new(base_path).join(project_name);
-    println!(
+    say!(
         "{}",
-        style(format!(
-            "Cloning repository to {}...",
-            project_path.display()
-        ))
-        .cyan()
+        style(
+            "NOTE: Some changes have been made to the project. Check README for more info, or simple run `cargo build` and run."
+        )
+        .yellow()
     );
 
-    println!();
+    say!();
 
-    println!(
+    setup_template(
+        project_path,
+        project_name,
+        template,
+        options,
+        options.vars.clone(),
+        |project_path| update_cargo_toml(project_path, project_name, options),
+    )?;
+
+    say!(
         "{}",
-        style(format!(
-            "NOTE: Some changes have been made to the project. Check README for more info, or simple run `cargo build` and run.",
-        ))
-        .yellow()
+        style("Rust project set up successfully!").green().bold()
     );
+    Ok(())
+}
 
-    println!();
+/// Sets up a template from the registry that genesis has no special
+/// handling for. The template's `genesis.toml` manifest, if any, drives the
+/// variables, replacements, commands and hooks.
+pub fn setup_custom_project(
+    base_path: &Path,
+    project_name: &str,
+    template: &Template,
+    options: &SetupOptions,
+) -> Result<(), GenesisError> {
+    say!(
+        "{}",
+        style(format!("Setting up {} project...", template.title())).yellow()
+    );
+    say!();
 
-    let _repo = clone_repo(RUST_URL, project_path.to_str().unwrap())?;
+    let project_path = base_path.join(project_name);
+    setup_template(
+        &project_path,
+        project_name,
+        template,
+        options,
+        options.vars.clone(),
+        |_| Ok(()),
+    )?;
 
-    // Update Cargo.toml
-    update_cargo_toml(&project_path, project_name)?;
+    say!(
+        "{}",
+        style(format!("{} project set up successfully!", template.title()))
+            .green()
+            .bold()
+    );
+    Ok(())
+}
 
-    println!("{}", style("Running setup commands...").cyan());
-    Command::new("cargo")
-        .arg("build")
-        .current_dir(&project_path)
-        .status()?;
+/// Clones the template to `project_path` and sets it up, the same way for
+/// every starter: post-clone hooks, then the `genesis.toml` manifest's
+/// variables, replacements and unused paths, post-render hooks, the setup
+/// commands and post-install hooks. `values` are those the starter already
+/// knows, e.g. the Go module path. Templates without a manifest are
+/// adapted by `rewrite` instead, and a manifest's commands replace the
+/// template's steps. A Go project the manifest removed paths from is
+/// compiled once the commands have run.
+fn setup_template(
+    project_path: &Path,
+    project_name: &str,
+    template: &Template,
+    options: &SetupOptions,
+    mut values: HashMap<String, String>,
+    rewrite: impl FnOnce(&Path) -> Result<(), GenesisError>,
+) -> Result<(), GenesisError> {
+    let (project_path, _scratch) = checkout_template(template, project_path, options)?;
 
-    // let _ = remove_dot_git_dir(project_path);
+    let manifest = Manifest::load(&project_path)?;
+    let hooks = project_hooks(template, manifest.as_ref());
+    run_hooks(
+        HookStage::PostClone,
+        &hooks,
+        &project_path,
+        &values,
+        options,
+    )?;
+
+    let mut steps = &template.steps;
+    let mut pruned = Vec::new();
+    if let Some(manifest) = &manifest {
+        values = manifest.resolve_variables(project_name, &values, options.interactive)?;
+        pruned = manifest.apply(&project_path, &values, options)?;
+        if !options.dry_run {
+            for path in &pruned {
+                say!("Removed unused path: {}", path);
+            }
+            say!("{}", style("Applied template manifest").green());
+        }
+
+        if !manifest.commands.is_empty() {
+            steps = &manifest.commands;
+        }
+    } else {
+        rewrite(&project_path)?;
+    }
+    run_hooks(
+        HookStage::PostRender,
+        &hooks,
+        &project_path,
+        &values,
+        options,
+    )?;
+
+    if !steps.is_empty() {
+        say!("{}", style("Running setup commands...").cyan());
+        run_steps(steps, &project_path, options)?;
+        say!();
+    }
+    // Templates given with `--template` don't declare a language.
+    let is_go = template.language == "go" || project_path.join("go.mod").is_file();
+    if is_go && !pruned.is_empty() {
+        verify_go_build(&project_path, &pruned, options)?;
+    }
+    run_hooks(
+        HookStage::PostInstall,
+        &hooks,
+        &project_path,
+        &values,
+        options,
+    )?;
 
-    println!(
-        "{}",
-        style("Rust project set up successfully!").green().bold()
-    );
     Ok(())
 }
 
-pub fn _remove_dot_git_dir(project_path: &Path) -> io::Result<()> {
-    Command::new("rm")
-        .arg("-rf")
-        .arg(".git")
-        .current_dir(&project_path)
-        .status()?;
+/// The hooks of the template's manifest, followed by the user's from the
+/// registry. Pre-clone hooks can only come from the registry, since the
+/// manifest isn't there until the template has been cloned.
+fn project_hooks(template: &Template, manifest: Option<&Manifest>) -> Hooks {
+    let mut hooks = Hooks::default();
+    if let Some(manifest) = manifest {
+        if !manifest.hooks.pre_clone.is_empty() {
+            log::warn!(
+                "Ignoring the pre-clone hooks in {}: they can only be set in the template registry",
+                MANIFEST_FILE
+            );
+        }
+        hooks.append(&Hooks {
+            pre_clone: Vec::new(),
+            ..manifest.hooks.clone()
+        });
+    }
+    hooks.append(&template.hooks);
+    hooks
+}
+
+/// The staging directories of the projects being generated.
+static STAGING_DIRS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Removes the staging directories of the projects being generated, for
+/// when genesis is interrupted, e.g. by Ctrl-C during `npm install`, and
+/// `generate_staged` never gets to clean up. The `genesis` command calls
+/// it from its signal handler; other tools that are interrupted while
+/// generating should too.
+pub fn remove_staging_dirs() {
+    let Ok(dirs) = STAGING_DIRS.lock() else {
+        return;
+    };
+    for dir in dirs.iter() {
+        let _ = fs::remove_dir_all(dir);
+    }
+}
+
+/// Keeps a staging directory listed for [`remove_staging_dirs`] while it's
+/// in use.
+struct StagingGuard(PathBuf);
+
+impl StagingGuard {
+    fn new(dir: &Path) -> Self {
+        if let Ok(mut dirs) = STAGING_DIRS.lock() {
+            dirs.push(dir.to_path_buf());
+        }
+        StagingGuard(dir.to_path_buf())
+    }
+}
+
+impl Drop for StagingGuard {
+    fn drop(&mut self) {
+        if let Ok(mut dirs) = STAGING_DIRS.lock() {
+            dirs.retain(|dir| *dir != self.0);
+        }
+    }
+}
+
+/// Generates a project in a staging directory next to its destination and
+/// moves it into place once `generate` succeeds, so a failed setup never
+/// leaves a half-initialized project behind. `generate` is given the
+/// directory to use as the base path.
+///
+/// With `keep_on_failure`, the staging directory of a failed setup is kept
+/// for debugging instead of being removed.
+pub fn generate_staged<F>(
+    base_path: &Path,
+    project_name: &str,
+    options: &SetupOptions,
+    generate: F,
+) -> Result<(), GenesisError>
+where
+    F: FnOnce(&Path) -> Result<(), GenesisError>,
+{
+    fs::create_dir_all(base_path)?;
+    // Staging inside the base path keeps the final move on one filesystem.
+    let staging = tempfile::Builder::new()
+        .prefix(".genesis-staging-")
+        .tempdir_in(base_path)?;
+    log::info!("Generating in {}", staging.path().display());
+    let _guard = StagingGuard::new(staging.path());
+
+    if let Err(e) = generate(staging.path()) {
+        if options.keep_on_failure {
+            let kept = staging.keep().join(project_name);
+            say!(
+                "{}",
+                style(format!("Kept the partial project at {}", kept.display())).yellow()
+            );
+        }
+        return Err(e);
+    }
+
+    let staged = staging.path().join(project_name);
+    let destination = base_path.join(project_name);
+    log::info!("Moving the project into {}", destination.display());
+    match options.existing {
+        ExistingDestination::Merge if destination.is_dir() => {
+            merge_project(&staged, &destination, options.interactive)
+        }
+        ExistingDestination::Overwrite if destination.is_dir() => {
+            fs::remove_dir_all(&destination)?;
+            Ok(fs::rename(&staged, &destination)?)
+        }
+        ExistingDestination::Overwrite if destination.exists() => {
+            fs::remove_file(&destination)?;
+            Ok(fs::rename(&staged, &destination)?)
+        }
+        _ => Ok(fs::rename(&staged, &destination)?),
+    }
+}
+
+/// Moves the files of a staged project into an existing directory. Files
+/// that already exist with other contents are only replaced once the user
+/// agrees, so nothing is merged when that can't be asked. The directory's
+/// own `.git` is always kept.
+fn merge_project(staged: &Path, destination: &Path, interactive: bool) -> Result<(), GenesisError> {
+    let mut files = Vec::new();
+    let mut conflicts = Vec::new();
+    for entry in WalkDir::new(staged)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+    {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let Ok(relative) = entry.path().strip_prefix(staged).map(Path::to_path_buf) else {
+            continue;
+        };
+        let target = destination.join(&relative);
+        if target.is_dir() {
+            return Err(GenesisError::Validation(format!(
+                "Cannot merge {}: it is a directory in {}",
+                relative.display(),
+                destination.display()
+            )));
+        }
+        if target.exists() && fs::read(&target)? != fs::read(entry.path())? {
+            conflicts.push(relative.clone());
+        }
+        files.push(relative);
+    }
+
+    if !conflicts.is_empty() && !interactive {
+        let conflicts: Vec<String> = conflicts.iter().map(|c| c.display().to_string()).collect();
+        return Err(GenesisError::Validation(format!(
+            "Cannot merge into {} without prompting, these files would be overwritten: {}",
+            destination.display(),
+            conflicts.join(", ")
+        )));
+    }
+
+    let mut resolve_all = None;
+    for relative in files {
+        let target = destination.join(&relative);
+        if conflicts.contains(&relative) {
+            let overwrite = match resolve_all {
+                Some(overwrite) => overwrite,
+                None => {
+                    let choice = Select::with_theme(&ColorfulTheme::default())
+                        .with_prompt(format!("{} already exists", relative.display()))
+                        .items(&[
+                            "Overwrite it",
+                            "Keep the existing file",
+                            "Overwrite all remaining conflicts",
+                            "Keep all remaining existing files",
+                        ])
+                        .default(1)
+                        .interact()?;
+                    if choice >= 2 {
+                        resolve_all = Some(choice == 2);
+                    }
+                    choice % 2 == 0
+                }
+            };
+            if !overwrite {
+                say!("Kept existing file: {}", relative.display());
+                continue;
+            }
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(staged.join(&relative), &target)?;
+    }
+
+    let git_dir = staged.join(".git");
+    if git_dir.exists() && !destination.join(".git").exists() {
+        fs::rename(git_dir, destination.join(".git"))?;
+    }
     Ok(())
 }
 
-pub fn update_genesis() -> Result<(), Box<dyn std::error::Error>> {
-    println!("{}", style("Checking for updates...").yellow());
+/// Clones the template to `project_path` and returns where the project now
+/// lives. For a dry run the template is cloned into a scratch directory
+/// instead, so the changes setup would make can still be worked out; the
+/// directory is removed when the returned `TempDir` is dropped.
+fn checkout_template(
+    template: &Template,
+    project_path: &Path,
+    options: &SetupOptions,
+) -> Result<(PathBuf, Option<TempDir>), GenesisError> {
+    let mut source = template.url.clone();
+    if let Some(git_ref) = &template.git_ref {
+        source.push_str(&format!(" at {}", git_ref));
+    }
+    if let Some(subdir) = &template.subdir {
+        source.push_str(&format!(" (path {})", subdir));
+    }
+
+    let dry_run = options.dry_run;
+    let (destination, scratch) = if dry_run {
+        print_dry_run(format!(
+            "Would clone {} into {}",
+            source,
+            options.destination.display()
+        ));
+        let scratch = tempfile::tempdir()?;
+        let scratch_path = scratch.path().join(project_path.file_name().unwrap());
+        (scratch_path, Some(scratch))
+    } else {
+        // The project is cloned into its staging directory, which is
+        // logged, but it's the destination people know it by.
+        say!(
+            "{}",
+            style(format!(
+                "Cloning {} to {}...",
+                source,
+                options.destination.display()
+            ))
+            .cyan()
+        );
+        say!();
+        (project_path.to_path_buf(), None)
+    };
+
+    // The commit is recorded so the project can be traced back to the exact
+    // revision it was generated from.
+    let commit = clone_template(template, &destination)?;
+    write_template_lock(&destination, template, commit.as_deref(), dry_run)?;
+    options.events.emit(Event::CloneDone {
+        url: template.url.clone(),
+        git_ref: template.git_ref.clone(),
+        path: template.subdir.clone(),
+        commit,
+    });
+    Ok((destination, scratch))
+}
+
+/// Clones the template, or copies it when it's a local directory, and
+/// returns the commit it's at.
+fn clone_template(
+    template: &Template,
+    project_path: &Path,
+) -> Result<Option<String>, GenesisError> {
+    let subdir = template.subdir.as_deref().map(Path::new);
+    if let Some(subdir) = subdir {
+        if !subdir
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(GenesisError::Template(format!(
+                "Template path '{}' must be relative to the repository root",
+                subdir.display()
+            )));
+        }
+    }
+
+    if let Some(source) = template.local_dir() {
+        if template.git_ref.is_some() {
+            say!(
+                "{}",
+                style("Ignoring the ref of a local template directory; use a file:// URL to check out a ref.")
+                    .yellow()
+            );
+        }
+        let template_dir = match subdir {
+            Some(subdir) => source.join(subdir),
+            None => source.clone(),
+        };
+        if !template_dir.is_dir() {
+            return Err(GenesisError::Template(format!(
+                "Template directory not found: {}",
+                template_dir.display()
+            )));
+        }
+        fs::create_dir_all(project_path)?;
+        copy_template_dir(&template_dir, project_path)?;
+
+        let commit = Repository::open(&source)
+            .and_then(|repo| Ok(repo.head()?.peel_to_commit()?.id().to_string()))
+            .ok();
+        return Ok(commit);
+    }
+
+    let Some(subdir) = subdir else {
+        let repo = clone_repo(
+            &template.url,
+            project_path,
+            template.git_ref.as_deref(),
+            template.shallow,
+            template.token.as_deref(),
+        )?;
+        let commit = repo.head()?.peel_to_commit()?.id();
+        return Ok(Some(commit.to_string()));
+    };
+
+    // Clone the whole repository next to the project, then keep only the
+    // template's directory as the project root.
+    let file_name = project_path.file_name().unwrap().to_string_lossy();
+    let checkout_path = project_path.with_file_name(format!(".{}.genesis-checkout", file_name));
+    let result = clone_template_subdir(template, subdir, &checkout_path, project_path);
+    if checkout_path.exists() {
+        fs::remove_dir_all(&checkout_path)?;
+    }
+    result
+}
 
+fn clone_template_subdir(
+    template: &Template,
+    subdir: &Path,
+    checkout_path: &Path,
+    project_path: &Path,
+) -> Result<Option<String>, GenesisError> {
+    let repo = clone_repo(
+        &template.url,
+        checkout_path,
+        template.git_ref.as_deref(),
+        template.shallow,
+        template.token.as_deref(),
+    )?;
+    let commit = repo.head()?.peel_to_commit()?.id();
+
+    let template_dir = checkout_path.join(subdir);
+    if !template_dir.is_dir() {
+        return Err(GenesisError::Template(format!(
+            "Template path '{}' not found in {}",
+            subdir.display(),
+            template.url
+        )));
+    }
+    fs::rename(&template_dir, project_path)?;
+    Ok(Some(commit.to_string()))
+}
+
+/// Compiles every package of a Go project, making sure no remaining file
+/// depends on code that was pruned for an unselected option.
+fn verify_go_build(
+    project_path: &Path,
+    pruned: &[String],
+    options: &SetupOptions,
+) -> Result<(), GenesisError> {
+    if options.dry_run {
+        print_dry_run("Would run: go build ./...");
+        return Ok(());
+    }
+    say!("{}", style("Verifying the project still builds...").cyan());
+
+    run_command("go", &["build", "./..."], project_path, options).map_err(|e| match e {
+        GenesisError::CommandFailed { .. } => GenesisError::Template(format!(
+            "Go project does not build after removing unused paths: {}",
+            pruned.join(", ")
+        )),
+        e => e,
+    })
+}
+
+/// Runs the template's steps, or its manifest's commands, through the
+/// shell like hooks, so they can use quoting, `&&`, pipes and variables.
+fn run_steps(
+    steps: &[String],
+    project_path: &Path,
+    options: &SetupOptions,
+) -> Result<(), GenesisError> {
+    for step in steps.iter().filter(|step| !step.trim().is_empty()) {
+        if options.dry_run {
+            print_dry_run(format!("Would run: {}", step));
+            continue;
+        }
+        let (mut process, shell) = shell_command(step);
+        run_process(&mut process, shell, step.clone(), project_path, options)?;
+    }
+    Ok(())
+}
+
+/// Runs a command in `dir` under a spinner, printing its output above the
+/// spinner as it arrives.
+fn run_command(
+    program: &str,
+    args: &[&str],
+    dir: &Path,
+    options: &SetupOptions,
+) -> Result<(), GenesisError> {
+    let command = [program]
+        .iter()
+        .chain(args)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ");
+    run_process(
+        Command::new(program).args(args),
+        program,
+        command,
+        dir,
+        options,
+    )
+}
+
+/// A `Command` running `command` through the shell, `sh -c` or `cmd /C` on
+/// Windows, and the shell's name for reporting it missing.
+pub(crate) fn shell_command(command: &str) -> (Command, &'static str) {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut process = Command::new(shell);
+    process.args([flag, command]);
+    (process, shell)
+}
+
+fn run_process(
+    process: &mut Command,
+    program: &str,
+    command: String,
+    dir: &Path,
+    options: &SetupOptions,
+) -> Result<(), GenesisError> {
+    log::info!("Running `{}` in {}", command, dir.display());
+    let status = run_streamed(process.current_dir(dir), program, &command)?;
+    options.events.emit(Event::Command {
+        command: command.clone(),
+        exit_code: status.code(),
+    });
+    if !status.success() {
+        return Err(GenesisError::CommandFailed { command, status });
+    }
+    say!("{} {}", style("✓").green().bold(), command);
+    Ok(())
+}
+
+/// Runs `process` to completion under a spinner labelled with `command`,
+/// printing its output above the spinner as it arrives. `program` is what
+/// gets reported as missing if it can't be started.
+pub(crate) fn run_streamed(
+    process: &mut Command,
+    program: &str,
+    command: &str,
+) -> Result<ExitStatus, GenesisError> {
+    let pb = progress::spinner(format!("Running {}...", command));
+    let child = process
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            pb.finish_and_clear();
+            return Err(GenesisError::spawn(program, e));
+        }
+    };
+
+    let stdout = child.stdout.take().map(|out| stream_lines(out, &pb));
+    let stderr = child.stderr.take().map(|err| stream_lines(err, &pb));
+    for stream in stdout.into_iter().chain(stderr) {
+        let _ = stream.join();
+    }
+    let status = child.wait();
+    pb.finish_and_clear();
+    Ok(status?)
+}
+
+fn stream_lines(stream: impl Read + Send + 'static, pb: &ProgressBar) -> thread::JoinHandle<()> {
+    let pb = pb.clone();
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            // Command output would break up JSON output on stdout.
+            if output::is_json() {
+                eprintln!("{}", line);
+            } else if output::shows_messages() {
+                pb.suspend(|| println!("  {}", line));
+            }
+            logging::to_file(&line);
+        }
+    })
+}
+
+pub fn update_genesis() -> Result<(), GenesisError> {
+    say!("{}", style("Checking for updates...").yellow());
+
+    let pb = progress::spinner("Installing the latest genesis...");
     let output = Command::new("cargo")
-        .args(&["install", "genesis_rs"])
-        .output()?;
+        .args(["install", "genesis_rs"])
+        .output();
+    pb.finish_and_clear();
+    let output = output.map_err(|e| GenesisError::spawn("cargo", e))?;
 
     if output.status.success() {
         let stdout = String::from_utf8_lossy(&output.stdout);
         if stdout.contains("Replacing") {
-            println!(
+            say!(
                 "{}",
                 style("Update successful! Genesis has been updated to the latest version.").green()
             );
         } else {
-            println!("{}", style("Genesis is already up to date.").yellow());
+            say!("{}", style("Genesis is already up to date.").yellow());
         }
     } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        println!("Update failed. Error: {}", stderr);
+        // Logged rather than printed, so it's still shown with `-q` or
+        // `--output json`.
+        log::error!(
+            "cargo install genesis_rs:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+        return Err(GenesisError::CommandFailed {
+            command: "cargo install genesis_rs".to_string(),
+            status: output.status,
+        });
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Held by tests that stage projects, as [`remove_staging_dirs`] removes
+    /// every test's staging directory.
+    static STAGING: Mutex<()> = Mutex::new(());
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut entries: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn moves_generated_projects_into_place() {
+        let _staging = STAGING.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().unwrap();
+        let options = SetupOptions::default();
+        generate_staged(dir.path(), "demo", &options, |staging| {
+            assert_ne!(staging, dir.path());
+            fs::create_dir_all(staging.join("demo")).unwrap();
+            Ok(fs::write(staging.join("demo/main.go"), "package main")?)
+        })
+        .unwrap();
+
+        assert_eq!(entries(dir.path()), ["demo"]);
+        assert_eq!(entries(&dir.path().join("demo")), ["main.go"]);
+        assert!(STAGING_DIRS.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rolls_back_failed_projects() {
+        let _staging = STAGING.lock().unwrap_or_else(|e| e.into_inner());
+        for keep_on_failure in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let options = SetupOptions {
+                keep_on_failure,
+                ..SetupOptions::default()
+            };
+            let result = generate_staged(dir.path(), "demo", &options, |staging| {
+                fs::create_dir_all(staging.join("demo")).unwrap();
+                fs::write(staging.join("demo/partial"), "").unwrap();
+                Err(GenesisError::Template("broken".to_string()))
+            });
+
+            assert!(matches!(result, Err(GenesisError::Template(_))));
+            assert!(!dir.path().join("demo").exists());
+            let left = entries(dir.path());
+            if keep_on_failure {
+                assert_eq!(left.len(), 1);
+                assert!(left[0].starts_with(".genesis-staging-"));
+                assert!(dir.path().join(&left[0]).join("demo/partial").exists());
+            } else {
+                assert!(left.is_empty(), "{:?}", left);
+            }
+        }
+    }
+
+    #[test]
+    fn removes_staging_dirs_when_interrupted() {
+        let _staging = STAGING.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().unwrap();
+        let options = SetupOptions::default();
+        let result = generate_staged(dir.path(), "demo", &options, |staging| {
+            fs::create_dir_all(staging.join("demo")).unwrap();
+            remove_staging_dirs();
+            assert!(!staging.exists());
+            Err(GenesisError::Template("interrupted".to_string()))
+        });
+
+        assert!(result.is_err());
+        assert!(entries(dir.path()).is_empty());
+    }
+
+    fn write(root: &Path, files: &[(&str, &str)]) {
+        for (path, content) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+    }
+
+    #[test]
+    fn checks_destinations() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(root, &[("file", ""), ("full/a.txt", "")]);
+        fs::create_dir(root.join("empty")).unwrap();
+
+        use ExistingDestination::*;
+        let cases = [
+            ("missing", Abort, None),
+            ("empty", Abort, None),
+            ("full", Abort, Some("--force")),
+            ("full", Overwrite, None),
+            ("full", Merge, None),
+            ("file", Abort, Some("is a file")),
+            ("file", Merge, Some("is a file")),
+            ("file", Overwrite, None),
+        ];
+        for (name, existing, error) in cases {
+            let result = check_destination(&root.join(name), existing);
+            match error {
+                None => assert!(result.is_ok(), "{} {:?}", name, existing),
+                Some(error) => assert!(
+                    result.unwrap_err().to_string().contains(error),
+                    "{} {:?}",
+                    name,
+                    existing
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn merges_into_existing_projects() {
+        let dir = tempfile::tempdir().unwrap();
+        let (staged, destination) = (dir.path().join("staged"), dir.path().join("dest"));
+        write(
+            &staged,
+            &[
+                ("README.md", "same"),
+                ("src/new.rs", "new"),
+                (".git/HEAD", "staged"),
+            ],
+        );
+        write(
+            &destination,
+            &[
+                ("README.md", "same"),
+                ("notes.txt", "mine"),
+                (".git/HEAD", "mine"),
+            ],
+        );
+
+        merge_project(&staged, &destination, false).unwrap();
+        assert_eq!(
+            entries(&destination),
+            [".git", "README.md", "notes.txt", "src"]
+        );
+        assert_eq!(
+            fs::read_to_string(destination.join("src/new.rs")).unwrap(),
+            "new"
+        );
+        assert_eq!(
+            fs::read_to_string(destination.join("notes.txt")).unwrap(),
+            "mine"
+        );
+        assert_eq!(
+            fs::read_to_string(destination.join(".git/HEAD")).unwrap(),
+            "mine"
+        );
+    }
+
+    #[test]
+    fn keeps_the_staged_repository_when_merging_into_a_plain_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let (staged, destination) = (dir.path().join("staged"), dir.path().join("dest"));
+        write(&staged, &[("a.txt", "a"), (".git/HEAD", "staged")]);
+        write(&destination, &[("b.txt", "b")]);
+
+        merge_project(&staged, &destination, false).unwrap();
+        assert_eq!(entries(&destination), [".git", "a.txt", "b.txt"]);
+        assert_eq!(
+            fs::read_to_string(destination.join(".git/HEAD")).unwrap(),
+            "staged"
+        );
+    }
+
+    #[test]
+    fn refuses_conflicting_merges_without_prompting() {
+        let dir = tempfile::tempdir().unwrap();
+        let (staged, destination) = (dir.path().join("staged"), dir.path().join("dest"));
+        write(
+            &staged,
+            &[("a.txt", "template"), ("b.txt", "same"), ("c.txt", "new")],
+        );
+        write(&destination, &[("a.txt", "mine"), ("b.txt", "same")]);
+
+        let error = merge_project(&staged, &destination, false).unwrap_err();
+        assert!(error.to_string().contains(": a.txt"), "{}", error);
+        assert_eq!(entries(&destination), ["a.txt", "b.txt"]);
+        assert_eq!(
+            fs::read_to_string(destination.join("a.txt")).unwrap(),
+            "mine"
+        );
+
+        write(&destination, &[("c.txt/inner", "")]);
+        let error = merge_project(&staged, &destination, false).unwrap_err();
+        assert!(error.to_string().contains("is a directory"), "{}", error);
+    }
+
+    #[test]
+    fn overwrites_existing_destinations() {
+        let _staging = STAGING.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), &[("demo/old.txt", "old")]);
+        let options = SetupOptions {
+            existing: ExistingDestination::Overwrite,
+            ..SetupOptions::default()
+        };
+        generate_staged(dir.path(), "demo", &options, |staging| {
+            write(staging, &[("demo/new.txt", "new")]);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(entries(dir.path()), ["demo"]);
+        assert_eq!(entries(&dir.path().join("demo")), ["new.txt"]);
+    }
+
+    #[test]
+    fn runs_manifest_hooks_before_the_users() {
+        let mut template = Template::from_source("https://example.com/starter.git");
+        template.hooks = Hooks {
+            pre_clone: vec!["user pre-clone".to_string()],
+            post_render: vec!["user post-render".to_string()],
+            ..Hooks::default()
+        };
+        let manifest: Manifest = toml_edit::de::from_str(
+            r#"
+            [hooks]
+            pre-clone = ["manifest pre-clone"]
+            post-render = ["manifest post-render"]
+            post-install = ["manifest post-install"]
+            "#,
+        )
+        .unwrap();
+
+        let hooks = project_hooks(&template, Some(&manifest));
+        assert_eq!(hooks.pre_clone, ["user pre-clone"]);
+        assert_eq!(
+            hooks.post_render,
+            ["manifest post-render", "user post-render"]
+        );
+        assert_eq!(hooks.post_install, ["manifest post-install"]);
+        assert_eq!(project_hooks(&template, None), template.hooks);
+    }
+}
@@ -0,0 +1,290 @@
+use console::style;
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::error::GenesisError;
+use crate::output::say;
+use crate::registry::Template;
+use crate::utils::print_dry_run;
+
+/// A program templates commonly need, and the arguments that make it print
+/// its version.
+pub struct Tool {
+    pub name: &'static str,
+    version_args: &'static [&'static str],
+}
+
+pub const TOOLS: &[Tool] = &[
+    Tool {
+        name: "git",
+        version_args: &["--version"],
+    },
+    Tool {
+        name: "go",
+        version_args: &["version"],
+    },
+    Tool {
+        name: "cargo",
+        version_args: &["--version"],
+    },
+    Tool {
+        name: "rustc",
+        version_args: &["--version"],
+    },
+    Tool {
+        name: "node",
+        version_args: &["--version"],
+    },
+    Tool {
+        name: "npm",
+        version_args: &["--version"],
+    },
+    Tool {
+        name: "pnpm",
+        version_args: &["--version"],
+    },
+    Tool {
+        name: "yarn",
+        version_args: &["--version"],
+    },
+    Tool {
+        name: "bun",
+        version_args: &["--version"],
+    },
+];
+
+/// Looks `program` up on the `PATH`, the way a shell would.
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    if program.contains(std::path::MAIN_SEPARATOR) {
+        let path = PathBuf::from(program);
+        return is_executable(&path).then_some(path);
+    }
+
+    let extensions: &[&str] = if cfg!(windows) {
+        &["", ".exe", ".cmd", ".bat"]
+    } else {
+        &[""]
+    };
+    env::split_paths(&env::var_os("PATH")?).find_map(|dir| {
+        extensions
+            .iter()
+            .map(|ext| dir.join(format!("{}{}", program, ext)))
+            .find(|path| is_executable(path))
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// The version `program` reports, e.g. `1.22.1` for go. Programs that
+/// aren't in `TOOLS` are asked with `--version`.
+pub fn detect_version(program: &str) -> Option<String> {
+    let args = TOOLS
+        .iter()
+        .find(|tool| tool.name == program)
+        .map_or(&["--version"][..], |tool| tool.version_args);
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_version(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Finds the first dotted version number in a program's output, such as
+/// `1.22.1` in `go version go1.22.1 linux/amd64`.
+fn parse_version(output: &str) -> Option<String> {
+    let version = regex::Regex::new(r"\d+(\.\d+)+").unwrap();
+    version.find(output).map(|m| m.as_str().to_string())
+}
+
+/// Compares dotted version numbers component by component, treating
+/// missing components as 0.
+fn version_at_least(found: &str, minimum: &str) -> bool {
+    let parse = |version: &str| -> Vec<u64> {
+        version
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    let (found, minimum) = (parse(found), parse(minimum));
+    for i in 0..found.len().max(minimum.len()) {
+        let (f, m) = (
+            found.get(i).copied().unwrap_or(0),
+            minimum.get(i).copied().unwrap_or(0),
+        );
+        if f != m {
+            return f > m;
+        }
+    }
+    true
+}
+
+/// The programs a template needs, with their minimum versions: those it
+/// declares under `requires`, and those its setup steps run.
+pub fn requirements(template: &Template) -> Vec<(String, Option<String>)> {
+    let mut requirements: Vec<(String, Option<String>)> = template
+        .requires
+        .iter()
+        .map(|(program, minimum)| {
+            let minimum = minimum.trim_start_matches(">=").trim();
+            (
+                program.clone(),
+                (!minimum.is_empty() && minimum != "*").then(|| minimum.to_string()),
+            )
+        })
+        .collect();
+    for program in template.steps.iter().flat_map(|step| step_programs(step)) {
+        if !requirements.iter().any(|(p, _)| p == program) {
+            requirements.push((program.to_string(), None));
+        }
+    }
+    requirements
+}
+
+/// The programs a step's shell command line runs, as far as can be told
+/// without a shell: the first word of each command in it, past variable
+/// assignments, leaving out shell builtins.
+fn step_programs(step: &str) -> Vec<&str> {
+    const BUILTINS: &[&str] = &[
+        ".", ":", "[", "cd", "echo", "exit", "export", "false", "set", "test", "true", "unset",
+    ];
+    step.split(['&', '|', ';', '(', ')', '\n'])
+        .filter_map(|command| command.split_whitespace().find(|word| !word.contains('=')))
+        .filter(|program| !BUILTINS.contains(program))
+        .collect()
+}
+
+/// Makes sure every program the template needs is installed, and recent
+/// enough, before anything is cloned. A dry run reports the programs that
+/// would stop the setup instead of failing, so the rest of it can still be
+/// previewed.
+pub fn preflight(template: &Template, dry_run: bool) -> Result<(), GenesisError> {
+    let requirements = requirements(template);
+    if requirements.is_empty() {
+        return Ok(());
+    }
+
+    say!("{}", style("Checking required tools...").cyan());
+    for (program, minimum) in requirements {
+        if find_program(&program).is_none() {
+            let error = GenesisError::ToolMissing(program);
+            if !dry_run {
+                return Err(error);
+            }
+            print_dry_run(format!("Would stop: {}", error));
+            continue;
+        }
+
+        let version = detect_version(&program);
+        log::debug!(
+            "Found {} {} for a minimum of {}",
+            program,
+            version.as_deref().unwrap_or("(unknown version)"),
+            minimum.as_deref().unwrap_or("any version")
+        );
+        match (&minimum, &version) {
+            (Some(minimum), Some(version)) if !version_at_least(version, minimum) => {
+                let error = GenesisError::ToolOutdated {
+                    tool: program,
+                    found: version.clone(),
+                    required: minimum.clone(),
+                };
+                if !dry_run {
+                    return Err(error);
+                }
+                print_dry_run(format!("Would stop: {}", error));
+            }
+            (Some(minimum), None) => say!(
+                "{} {} (could not determine its version, {} or newer is required)",
+                style("!").yellow().bold(),
+                program,
+                minimum
+            ),
+            _ => say!(
+                "{} {} {}",
+                style("✓").green().bold(),
+                program,
+                version.as_deref().unwrap_or("")
+            ),
+        }
+    }
+    say!();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_versions() {
+        let cases = [
+            ("go version go1.22.1 linux/amd64", Some("1.22.1")),
+            ("cargo 1.79.0 (ffa9cf99a 2024-06-03)", Some("1.79.0")),
+            ("rustc 1.79.0 (129f3b996 2024-06-10)", Some("1.79.0")),
+            ("v20.11.1\n", Some("20.11.1")),
+            ("10.5.0", Some("10.5.0")),
+            ("git version 2.39.5", Some("2.39.5")),
+            ("1.1.20+abc", Some("1.1.20")),
+            ("version 3", None),
+            ("", None),
+        ];
+        for (output, expected) in cases {
+            assert_eq!(parse_version(output).as_deref(), expected, "{}", output);
+        }
+    }
+
+    #[test]
+    fn finds_the_programs_steps_run() {
+        let cases: [(&str, &[&str]); 6] = [
+            ("go mod tidy", &["go"]),
+            ("go mod tidy && go generate ./...", &["go", "go"]),
+            ("cd web && npm install", &["npm"]),
+            (
+                "CGO_ENABLED=0 go build ./... | tee build.log",
+                &["go", "tee"],
+            ),
+            ("test -f .env || cp .env.example .env", &["cp"]),
+            ("", &[]),
+        ];
+        for (step, expected) in cases {
+            assert_eq!(step_programs(step), expected, "{}", step);
+        }
+    }
+
+    #[test]
+    fn compares_versions() {
+        let cases = [
+            ("1.22.1", "1.22", true),
+            ("1.22", "1.22.0", true),
+            ("1.22.0", "1.22.1", false),
+            ("1.10", "1.9", true),
+            ("1.9", "1.10", false),
+            ("2", "1.99.99", true),
+            ("20.11.1", "18", true),
+            ("0.9", "1", false),
+            ("1.2.3", "1.2.3", true),
+        ];
+        for (found, minimum, expected) in cases {
+            assert_eq!(
+                version_at_least(found, minimum),
+                expected,
+                "{} >= {}",
+                found,
+                minimum
+            );
+        }
+    }
+}
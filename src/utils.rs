@@ -2,30 +2,47 @@ use console::{style, Term};
 use dialoguer::{theme::ColorfulTheme, Select};
 
 use colored::*;
-use regex;
-use std::{fs, io::Write, path::Path};
-use toml_edit::{Document, Item};
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+use toml_edit::{table, value, Document, Item};
+
+use crate::constants::TEMPLATE_LOCK_FILE;
+use crate::error::GenesisError;
+use crate::event::{Event, RewriteAction};
+use crate::output::say;
+use crate::registry::Template;
+use crate::setup::SetupOptions;
 
 pub fn prompt_step<T>(
     term: &Term,
     prompt: &str,
-    input_fn: impl FnOnce() -> Result<T, Box<dyn std::error::Error>>,
-) -> Result<T, Box<dyn std::error::Error>> {
+    input_fn: impl FnOnce() -> Result<T, GenesisError>,
+) -> Result<T, GenesisError> {
     term.clear_last_lines(2)?;
-    println!("{}", style(prompt).cyan().bold());
+    say!("{}", style(prompt).cyan().bold());
     let result = input_fn()?;
     term.clear_last_lines(1)?;
-    println!("{} {}", style("✓").green().bold(), style(prompt).dim());
+    say!("{} {}", style("✓").green().bold(), style(prompt).dim());
     Ok(result)
 }
 
-pub fn update_cargo_toml(
+pub(crate) fn update_cargo_toml(
     project_path: &Path,
     project_name: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
+    options: &SetupOptions,
+) -> Result<(), GenesisError> {
     let cargo_toml_path = project_path.join("Cargo.toml");
     let cargo_toml_content = fs::read_to_string(&cargo_toml_path)?;
-    let mut doc = cargo_toml_content.parse::<Document>()?;
+    let mut doc = cargo_toml_content.parse::<Document>().map_err(|e| {
+        GenesisError::Template(format!(
+            "Invalid Cargo.toml in {}: {}",
+            project_path.display(),
+            e
+        ))
+    })?;
 
     if let Some(package) = doc.as_table_mut().get_mut("package") {
         if let Some(name) = package.get_mut("name") {
@@ -33,15 +50,65 @@ pub fn update_cargo_toml(
         }
     }
 
+    if options.dry_run {
+        print_dry_run(format!(
+            "Would set the package name in Cargo.toml to '{}'",
+            project_name
+        ));
+        return Ok(());
+    }
+
     let updated_content = doc.to_string();
     let mut file = fs::File::create(&cargo_toml_path)?;
     file.write_all(updated_content.as_bytes())?;
 
-    println!("{}", style("Updated project name in Cargo.toml!").green());
+    say!("{}", style("Updated project name in Cargo.toml!").green());
+    options.events.emit(Event::FilesRewritten {
+        action: RewriteAction::PackageName,
+        placeholder: None,
+        files: vec!["Cargo.toml".to_string()],
+    });
+    Ok(())
+}
+
+/// Records which template, and which commit of it, a project was generated
+/// from.
+pub(crate) fn write_template_lock(
+    project_path: &Path,
+    template: &Template,
+    commit: Option<&str>,
+    dry_run: bool,
+) -> Result<(), GenesisError> {
+    if dry_run {
+        print_dry_run(format!(
+            "Would record the template{} in {}",
+            commit
+                .map(|c| format!(" at commit {}", c))
+                .unwrap_or_default(),
+            TEMPLATE_LOCK_FILE
+        ));
+        return Ok(());
+    }
+
+    let mut doc = Document::new();
+    doc["template"] = table();
+    doc["template"]["name"] = value(template.name.as_str());
+    doc["template"]["url"] = value(template.url.as_str());
+    if let Some(git_ref) = &template.git_ref {
+        doc["template"]["ref"] = value(git_ref.as_str());
+    }
+    if let Some(subdir) = &template.subdir {
+        doc["template"]["path"] = value(subdir.as_str());
+    }
+    if let Some(commit) = commit {
+        doc["template"]["commit"] = value(commit);
+    }
+
+    fs::write(project_path.join(TEMPLATE_LOCK_FILE), doc.to_string())?;
     Ok(())
 }
 
-pub fn prompt_database_selection(term: &Term) -> Result<String, Box<dyn std::error::Error>> {
+pub(crate) fn prompt_database_selection(term: &Term) -> Result<String, GenesisError> {
     prompt_step(term, "Choose your database:", || {
         let options = &["MongoDB", "PostgreSQL"];
         let selection = Select::with_theme(&ColorfulTheme::default())
@@ -52,46 +119,107 @@ pub fn prompt_database_selection(term: &Term) -> Result<String, Box<dyn std::err
     })
 }
 
+pub(crate) fn prompt_rust_template(term: &Term) -> Result<String, GenesisError> {
+    prompt_step(term, "Choose your Rust project type:", || {
+        let options = &["Basic Rust Project", "Full Starter template"];
+        let templates = ["basic", "full"];
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .items(&options[..])
+            .default(0)
+            .interact_on(term)?;
+        Ok(templates[selection].to_string())
+    })
+}
+
+/// Fails with a hint about the missing flag when no prompt can be shown.
+pub fn require_interactive(interactive: bool, flag: &str) -> Result<(), GenesisError> {
+    if interactive {
+        return Ok(());
+    }
+    Err(GenesisError::Validation(format!(
+        "missing required value: pass `--{}` when running non-interactively",
+        flag
+    )))
+}
+
+/// The desktop directory when the platform has one, otherwise the current
+/// directory (e.g. on headless Linux machines).
+pub fn default_project_path() -> Result<PathBuf, GenesisError> {
+    match dirs::desktop_dir() {
+        Some(path) => Ok(path),
+        None => Ok(env::current_dir()?),
+    }
+}
+
+/// Expands `~` and environment variables such as `$HOME` in a user-supplied path.
+pub fn expand_path(path: &str) -> Result<PathBuf, GenesisError> {
+    let expanded = shellexpand::full(path).map_err(|e| {
+        GenesisError::Validation(format!("Could not expand path '{}': {}", path, e))
+    })?;
+    Ok(PathBuf::from(expanded.into_owned()))
+}
+
+/// Parses a `KEY=VALUE` pair given with `--var`.
+pub fn parse_var(var: &str) -> Result<(String, String), GenesisError> {
+    match var.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(GenesisError::Validation(format!(
+            "Invalid variable '{}', expected KEY=VALUE",
+            var
+        ))),
+    }
+}
+
 pub fn print_banner() {
-    println!("{}", "\n".repeat(2));
-    println!(
+    say!("{}", "\n".repeat(2));
+    say!(
         "{}",
         r#"   ______                      _     "#.bright_cyan()
     );
-    println!(
+    say!(
         "{}",
         r#"  / ____/___  ____  ___  _____(_)____"#.bright_cyan()
     );
-    println!(
+    say!(
         "{}",
         r#" / / __/ __ \/ __ \/ _ \/ ___/ / ___/"#.bright_cyan()
     );
-    println!(
+    say!(
         "{}",
         r#"/ /_/ / /_/ / / / /  __(__  ) (__  ) "#.bright_cyan()
     );
-    println!(
+    say!(
         "{}",
         r#"\____/\____/_/ /_/\___/____/_/____/  "#.bright_cyan()
     );
-    println!("{}", "\n".repeat(2));
-    println!(
+    say!("{}", "\n".repeat(2));
+    say!(
         "{}",
         "Set up your projects in seconds using Genesis"
             .bright_green()
             .bold()
     );
-    println!(
+    say!(
         "{}",
         "---------------------------------------------".bright_green()
     );
-    println!("{}", "\n".repeat(1));
+    say!("\n");
 }
 
-pub fn update_database_config(
+/// Reports an action that `--dry-run` skipped.
+pub fn print_dry_run(action: impl std::fmt::Display) {
+    say!("{} {}", style("[dry-run]").magenta().bold(), action);
+}
+
+pub(crate) fn update_database_config(
     project_path: &Path,
     database: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
+    options: &SetupOptions,
+) -> Result<(), GenesisError> {
+    let dry_run = options.dry_run;
+    let mut rewritten = Vec::new();
     let main_go_path = project_path.join("cmd").join("main.go");
     if main_go_path.exists() {
         let mut content = fs::read_to_string(&main_go_path)?;
@@ -130,19 +258,25 @@ pub fn update_database_config(
     // Initialize user usecase with PostgreSQL repository
     userUsecase := config.InitializeUsecasesPostgres(userRepo)"#;
 
-        if content.contains(old_mongo_code) || content.contains(old_postgres_code) {
+        if (content.contains(old_mongo_code) || content.contains(old_postgres_code)) && dry_run {
+            print_dry_run(format!(
+                "Would update cmd/main.go to initialize the {} repository",
+                database
+            ));
+        } else if content.contains(old_mongo_code) || content.contains(old_postgres_code) {
             content = content.replace(old_mongo_code, new_init_code);
             content = content.replace(old_postgres_code, new_init_code);
             fs::write(main_go_path, content)?;
-            println!(
+            rewritten.push("cmd/main.go".to_string());
+            say!(
                 "{}",
                 style("Updated main.go with selected database").green()
             );
         } else {
-            println!("{}", style("Couldn't find expected database initialization in main.go. Manual update may be required.").yellow());
+            say!("{}", style("Couldn't find expected database initialization in main.go. Manual update may be required.").yellow());
         }
     } else {
-        println!(
+        say!(
             "{}",
             style("main.go not found in cmd directory. Skipping update.").yellow()
         );
@@ -184,21 +318,60 @@ func NewUserUsecase(repo postgres.Interface, emailService *utils.EmailService) *
 
         // let old_user_code_regex = regex::Regex::new(r"type UserUsecase struct \{[\s\S]*?func NewUserUsecase\([^)]*\) \*UserUsecase \{[\s\S]*?\}").unwrap();
         let old_user_code_regex = regex::Regex::new(r"(?m)^type UserUsecase struct \{[\s\S]*?^func NewUserUsecase\([^)]*\) \*UserUsecase \{[\s\S]*?^\}").unwrap();
-        if old_user_code_regex.is_match(&content) {
+        if old_user_code_regex.is_match(&content) && dry_run {
+            print_dry_run(format!(
+                "Would update internal/application/usecases/user/user.go to use the {} repository",
+                database
+            ));
+        } else if old_user_code_regex.is_match(&content) {
             content = old_user_code_regex
                 .replace_all(&content, new_user_code)
                 .to_string();
             fs::write(user_go_path, content)?;
-            println!(
+            rewritten.push("internal/application/usecases/user/user.go".to_string());
+            say!(
                 "{}",
                 style("Updated user.go with selected database").green()
             );
         } else {
-            println!("{}", style("Couldn't find expected UserUsecase struct in user.go. Manual update may be required.").yellow());
+            say!("{}", style("Couldn't find expected UserUsecase struct in user.go. Manual update may be required.").yellow());
         }
     } else {
-        println!("{}", style("user.go not found. Skipping update.").yellow());
+        say!("{}", style("user.go not found. Skipping update.").yellow());
     }
 
+    if !rewritten.is_empty() {
+        options.events.emit(Event::FilesRewritten {
+            action: RewriteAction::Database,
+            placeholder: None,
+            files: rewritten,
+        });
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_vars() {
+        let cases = [
+            ("author=Me", Some(("author", "Me"))),
+            (" author =Me Too", Some(("author", "Me Too"))),
+            (
+                "url=https://x.dev/?a=b",
+                Some(("url", "https://x.dev/?a=b")),
+            ),
+            ("empty=", Some(("empty", ""))),
+            ("author", None),
+            ("=Me", None),
+            (" =Me", None),
+        ];
+        for (var, expected) in cases {
+            let parsed = parse_var(var).ok();
+            let parsed = parsed.as_ref().map(|(k, v)| (k.as_str(), v.as_str()));
+            assert_eq!(parsed, expected, "{}", var);
+        }
+    }
+}
@@ -0,0 +1,379 @@
+/// Words Cargo refuses as package names: Rust keywords, the names of the
+/// standard crates and the directories Cargo builds into.
+const RESERVED_CRATE_NAMES: &[&str] = &[
+    "abstract",
+    "as",
+    "async",
+    "await",
+    "become",
+    "box",
+    "break",
+    "const",
+    "continue",
+    "crate",
+    "do",
+    "dyn",
+    "else",
+    "enum",
+    "extern",
+    "false",
+    "final",
+    "fn",
+    "for",
+    "if",
+    "impl",
+    "in",
+    "let",
+    "loop",
+    "macro",
+    "match",
+    "mod",
+    "move",
+    "mut",
+    "override",
+    "priv",
+    "pub",
+    "ref",
+    "return",
+    "self",
+    "static",
+    "struct",
+    "super",
+    "trait",
+    "true",
+    "try",
+    "type",
+    "typeof",
+    "unsafe",
+    "unsized",
+    "use",
+    "virtual",
+    "where",
+    "while",
+    "yield",
+    "alloc",
+    "core",
+    "proc_macro",
+    "std",
+    "test",
+    "build",
+    "deps",
+    "examples",
+    "incremental",
+];
+
+/// Checks that `name` can be used as the name of a new project directory,
+/// and as a package name for the template's `language`: a crate name for
+/// Rust and an npm package name for JavaScript projects. The error suggests
+/// a valid name when one can be derived, e.g. `my-app` for "My App".
+pub fn validate_project_name(name: &str, language: &str) -> Result<(), String> {
+    let problem = match language {
+        "rust" => directory_name_problem(name).or_else(|| crate_name_problem(name)),
+        "react" | "javascript" | "typescript" | "node" => {
+            directory_name_problem(name).or_else(|| npm_name_problem(name))
+        }
+        _ => directory_name_problem(name),
+    };
+
+    let Some(problem) = problem else {
+        return Ok(());
+    };
+    match suggest_name(name).filter(|s| s != name && validate_project_name(s, language).is_ok()) {
+        Some(suggestion) => Err(format!(
+            "Invalid project name '{}': {} (try `{}`)",
+            name, problem, suggestion
+        )),
+        None => Err(format!("Invalid project name '{}': {}", name, problem)),
+    }
+}
+
+fn directory_name_problem(name: &str) -> Option<&'static str> {
+    if name.trim().is_empty() {
+        Some("it is empty")
+    } else if name == "." || name == ".." {
+        Some("it refers to an existing directory")
+    } else if name.contains(['/', '\\']) {
+        Some("it contains a path separator; use --path to choose where the project is created")
+    } else if name.chars().any(char::is_control) {
+        Some("it contains control characters")
+    } else if name.contains(char::is_whitespace) {
+        // Valid on most filesystems, but breaks unquoted paths in shells,
+        // Makefiles and scripts.
+        Some("it contains whitespace")
+    } else {
+        None
+    }
+}
+
+fn crate_name_problem(name: &str) -> Option<&'static str> {
+    if name.len() > 64 {
+        Some("crate names are limited to 64 characters")
+    } else if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Some("crate names may only contain ASCII letters, digits, `-` and `_`")
+    } else if name.starts_with(|c: char| c.is_ascii_digit()) {
+        Some("crate names cannot start with a digit")
+    } else if RESERVED_CRATE_NAMES.contains(&name.replace('-', "_").as_str()) {
+        Some("it is a Rust keyword or reserved by Cargo")
+    } else {
+        None
+    }
+}
+
+fn npm_name_problem(name: &str) -> Option<&'static str> {
+    if name.len() > 214 {
+        Some("npm package names are limited to 214 characters")
+    } else if name.starts_with(['.', '_']) {
+        Some("npm package names cannot start with `.` or `_`")
+    } else if name.chars().any(|c| c.is_ascii_uppercase()) {
+        Some("npm package names must be lowercase")
+    } else if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        Some("npm package names may only contain lowercase letters, digits, `-`, `_` and `.`")
+    } else if name == "node_modules" || name == "favicon.ico" {
+        Some("it is reserved by npm")
+    } else {
+        None
+    }
+}
+
+/// Lowercases `name` and joins its words with `-`, dropping anything else
+/// that isn't a letter or digit.
+fn suggest_name(name: &str) -> Option<String> {
+    let words: Vec<String> = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect();
+    (!words.is_empty()).then(|| words.join("-"))
+}
+
+/// Checks `path` against Go's module path syntax: `/`-separated elements
+/// made of ASCII letters, digits and `-._~`, none of them empty or starting
+/// or ending with a dot, and a lowercase first element such as
+/// `github.com`.
+pub fn validate_module_path(path: &str) -> Result<(), String> {
+    let problem = if path.is_empty() {
+        Some("it is empty")
+    } else if path.starts_with('/') || path.ends_with('/') {
+        Some("it cannot start or end with `/`")
+    } else if path.split('/').any(str::is_empty) {
+        Some("it cannot contain empty elements (`//`)")
+    } else if !path
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '-' | '.' | '_' | '~'))
+    {
+        Some("module paths may only contain ASCII letters, digits, `/`, `-`, `.`, `_` and `~`")
+    } else if path
+        .split('/')
+        .any(|element| element.starts_with('.') || element.ends_with('.'))
+    {
+        Some("path elements cannot start or end with `.`")
+    } else if path
+        .split('/')
+        .next()
+        .is_some_and(|first| first.chars().any(|c| c.is_ascii_uppercase()))
+    {
+        Some("the first path element must be lowercase")
+    } else {
+        None
+    };
+
+    let Some(problem) = problem else {
+        return Ok(());
+    };
+    match suggest_module_path(path).filter(|s| s != path && validate_module_path(s).is_ok()) {
+        Some(suggestion) => Err(format!(
+            "Invalid Go module path '{}': {} (try `{}`)",
+            path, problem, suggestion
+        )),
+        None => Err(format!("Invalid Go module path '{}': {}", path, problem)),
+    }
+}
+
+fn suggest_module_path(path: &str) -> Option<String> {
+    let elements: Vec<String> = path
+        .split(['/', '\\'])
+        .enumerate()
+        .filter_map(|(i, element)| {
+            let element = element
+                .split(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~')))
+                .filter(|word| !word.is_empty())
+                .collect::<Vec<_>>()
+                .join("-");
+            let element = element.trim_matches('.');
+            let element = if i == 0 {
+                element.to_ascii_lowercase()
+            } else {
+                element.to_string()
+            };
+            (!element.is_empty()).then_some(element)
+        })
+        .collect();
+    (!elements.is_empty()).then(|| elements.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_project_names() {
+        let cases = [
+            ("my-app", "rust"),
+            ("my_app2", "rust"),
+            ("a", "rust"),
+            ("my-app", "react"),
+            ("my.app", "typescript"),
+            ("@scope", "go"),
+            ("type", "go"),
+            ("node_modules", "rust"),
+        ];
+        for (name, language) in cases {
+            assert!(
+                validate_project_name(name, language).is_ok(),
+                "{} ({})",
+                name,
+                language
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_project_names() {
+        let long_crate = "a".repeat(65);
+        let long_package = "a".repeat(215);
+        let cases = [
+            ("", "go", "it is empty"),
+            ("  ", "go", "it is empty"),
+            ("..", "go", "existing directory"),
+            ("a/b", "go", "path separator"),
+            ("a\\b", "go", "path separator"),
+            (" app", "go", "whitespace"),
+            ("a\tb", "go", "control characters"),
+            ("My App", "go", "whitespace"),
+            ("My App", "custom", "whitespace"),
+            ("My App", "rust", "whitespace"),
+            ("my.app", "rust", "ASCII letters"),
+            ("2fast", "rust", "start with a digit"),
+            (long_crate.as_str(), "rust", "64 characters"),
+            ("type", "rust", "reserved"),
+            ("proc-macro", "rust", "reserved"),
+            ("deps", "rust", "reserved"),
+            ("MyApp", "react", "lowercase"),
+            ("_app", "node", "cannot start with"),
+            (".app", "javascript", "cannot start with"),
+            ("my app", "typescript", "whitespace"),
+            ("my+app", "typescript", "may only contain"),
+            ("node_modules", "react", "reserved by npm"),
+            (long_package.as_str(), "react", "214 characters"),
+        ];
+        for (name, language, problem) in cases {
+            let error = validate_project_name(name, language).unwrap_err();
+            assert!(
+                error.contains(problem),
+                "{} ({}): {}",
+                name,
+                language,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn suggests_valid_project_names() {
+        let cases = [
+            ("My App", "go", Some("my-app")),
+            ("My App", "custom", Some("my-app")),
+            ("My App", "rust", Some("my-app")),
+            ("My App", "react", Some("my-app")),
+            ("MyApp", "react", Some("myapp")),
+            ("hello world!", "rust", Some("hello-world")),
+            ("_app", "node", Some("app")),
+            ("type", "rust", None),
+            ("2fast", "rust", None),
+            ("!!!", "rust", None),
+        ];
+        for (name, language, expected) in cases {
+            let error = validate_project_name(name, language).unwrap_err();
+            let suggestion = error
+                .split_once("(try `")
+                .map(|(_, rest)| rest.trim_end_matches("`)"));
+            assert_eq!(suggestion, expected, "{} ({}): {}", name, language, error);
+            if let Some(suggestion) = suggestion {
+                assert!(validate_project_name(suggestion, language).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn accepts_valid_module_paths() {
+        let cases = [
+            "github.com/acme/service",
+            "example.com/Acme/My_Service.v2",
+            "acme",
+            "gopkg.in/yaml.v3",
+            "example.com/~user/x",
+        ];
+        for path in cases {
+            assert!(validate_module_path(path).is_ok(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_module_paths() {
+        let cases = [
+            ("", "it is empty", None),
+            (
+                "/github.com/acme",
+                "start or end with `/`",
+                Some("github.com/acme"),
+            ),
+            (
+                "github.com/acme/",
+                "start or end with `/`",
+                Some("github.com/acme"),
+            ),
+            (
+                "github.com//acme",
+                "empty elements",
+                Some("github.com/acme"),
+            ),
+            (
+                "github.com/acme/my service",
+                "may only contain",
+                Some("github.com/acme/my-service"),
+            ),
+            (
+                "github.com/.acme",
+                "start or end with `.`",
+                Some("github.com/acme"),
+            ),
+            (
+                "GitHub.com/acme",
+                "must be lowercase",
+                Some("github.com/acme"),
+            ),
+            (
+                "github.com\\acme",
+                "may only contain",
+                Some("github.com/acme"),
+            ),
+        ];
+        for (path, problem, expected) in cases {
+            let error = validate_module_path(path).unwrap_err();
+            assert!(error.contains(problem), "{}: {}", path, error);
+            let suggestion = error
+                .split_once("(try `")
+                .map(|(_, rest)| rest.trim_end_matches("`)"));
+            assert_eq!(suggestion, expected, "{}: {}", path, error);
+            if let Some(suggestion) = suggestion {
+                assert!(validate_module_path(suggestion).is_ok());
+            }
+        }
+    }
+}